@@ -1,12 +1,12 @@
 use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Generate Warp launch config for editor window
 pub fn generate_editor_config(
-    worktree_path: &PathBuf,
+    worktree_path: &Path,
     editor: &str,
     pre_commands: &[String],
 ) -> Result<PathBuf, Box<dyn Error>> {
@@ -47,23 +47,17 @@ windows:
     Ok(config_path)
 }
 
-/// Generate Warp launch config for a session (Claude or Codex)
+/// Generate Warp launch config that runs `resume_cmd` for a session
 pub fn generate_session_config(
     session_uuid: &str,
-    worktree_path: &PathBuf,
+    worktree_path: &Path,
     title: &str,
-    provider: &str,
+    resume_cmd: &str,
     pre_commands: &[String],
 ) -> Result<PathBuf, Box<dyn Error>> {
     let config_name = format!("ws-session-{}", &session_uuid[..8.min(session_uuid.len())]);
     let config_path = warp_config_dir()?.join(format!("{}.yaml", config_name));
 
-    // Generate provider-specific resume command
-    let resume_cmd = match provider {
-        "codex" => format!("codex resume {}", session_uuid),
-        _ => format!("claude --resume {}", session_uuid),
-    };
-
     // Build commands list: pre_commands first, then resume
     let mut commands = String::new();
     for cmd in pre_commands {
@@ -93,7 +87,7 @@ windows:
 }
 
 /// Open a Warp launch config using warp:// URI scheme
-pub fn open_config(config_path: &PathBuf) -> Result<(), Box<dyn Error>> {
+pub fn open_config(config_path: &Path) -> Result<(), Box<dyn Error>> {
     // Extract the config name (filename without .yaml extension)
     let config_name = config_path
         .file_stem()
@@ -115,10 +109,8 @@ pub fn cleanup_old_configs() -> Result<(), Box<dyn Error>> {
     let pattern = config_dir.join("ws-*.yaml");
     let pattern_str = pattern.to_string_lossy();
 
-    for entry in glob::glob(&pattern_str)? {
-        if let Ok(path) = entry {
-            let _ = fs::remove_file(path);
-        }
+    for path in glob::glob(&pattern_str)?.flatten() {
+        let _ = fs::remove_file(path);
     }
 
     Ok(())
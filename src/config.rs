@@ -1,26 +1,281 @@
+use crate::scanner::Registry;
+use crate::scanner::claude::{self, TokenUsage};
+use crate::scanner::codex;
+use ratatui::style::Color;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Deserialize, Clone)]
 pub struct Config {
     #[serde(default = "default_scan_dirs")]
-    pub scan_dirs: Vec<String>,
+    pub scan_dirs: Vec<ScanDir>,
 
     #[serde(default = "default_editor")]
     pub editor: String,
 
     #[serde(default = "default_scan_on_open")]
     pub scan_on_open: bool,
+
+    /// How far back the unfiltered tree looks for sessions, e.g. `"14d"` or `"all"`
+    #[serde(default)]
+    pub recent_window: RecentWindow,
+
+    /// Extra columns on session lines, e.g. `["model", "tokens", "cost"]`
+    #[serde(default)]
+    pub session_columns: Vec<SessionColumn>,
+
+    /// Prices by model name prefix, on top of the built-in ones
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+
+    /// Session providers for agents without built-in support
+    #[serde(default)]
+    pub providers: Vec<ProviderConfig>,
+
+    /// Agent homes to read besides the usual one, by provider, e.g.
+    /// `claude = ["~/.claude-work"]`
+    #[serde(default)]
+    pub homes: HashMap<String, Vec<String>>,
+}
+
+/// An agent whose sessions are JSON files, declared in the config:
+///
+/// ```toml
+/// [[providers]]
+/// name = "myagent"
+/// sessions = "~/.myagent/sessions/*.jsonl"
+/// resume = "myagent --resume {uuid}"
+/// fields = { id = "/session_id", cwd = "/cwd", prompt = "/user_message" }
+/// ```
+#[derive(Deserialize, Clone, Debug)]
+pub struct ProviderConfig {
+    /// Stored with its sessions and matched by `provider:` filters
+    pub name: String,
+    /// Shown in the preview and in untitled session tabs; defaults to `name`
+    pub display_name: Option<String>,
+    /// Glob matching the session files, one session per file
+    pub sessions: String,
+    #[serde(default)]
+    pub format: SessionFormat,
+    /// Resume command; `{uuid}` is replaced by the session id
+    pub resume: String,
+    /// A color name or `#rrggbb` for its sessions in the tree
+    pub color: Option<String>,
+    #[serde(default)]
+    pub fields: SessionFields,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionFormat {
+    /// One JSON record per line
+    #[default]
+    Jsonl,
+    /// One JSON document per file
+    Json,
+}
+
+/// JSON pointers (RFC 6901, e.g. `/payload/cwd`) to a session's fields.
+///
+/// Each is looked up in every record: `id`, `cwd` and `branch` take the first
+/// value found, every `prompt` counts as a message, and the latest
+/// `timestamp` (RFC 3339, or seconds or ms since epoch) is the last activity.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct SessionFields {
+    pub id: Option<String>,
+    pub cwd: Option<String>,
+    pub branch: Option<String>,
+    pub prompt: Option<String>,
+    /// If set, prompts are only taken from records whose role here is `user`
+    pub role: Option<String>,
+    pub timestamp: Option<String>,
+    /// For `json` files, an array whose elements are records too, e.g. `/messages`
+    pub messages: Option<String>,
+}
+
+/// Optional usage columns shown on session lines in the tree
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionColumn {
+    Model,
+    Tokens,
+    Cost,
+}
+
+/// USD per million tokens. Cache prices default to the input price.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    pub cache_write: Option<f64>,
+    pub cache_read: Option<f64>,
+}
+
+impl ModelPrice {
+    const fn new(input: f64, output: f64, cache_write: f64, cache_read: f64) -> Self {
+        ModelPrice {
+            input,
+            output,
+            cache_write: Some(cache_write),
+            cache_read: Some(cache_read),
+        }
+    }
+
+    /// Estimated cost in USD
+    pub fn cost(&self, tokens: &TokenUsage) -> f64 {
+        let per_token = |price: f64, count: i64| price * count as f64 / 1_000_000.0;
+        per_token(self.input, tokens.input)
+            + per_token(self.output, tokens.output)
+            + per_token(
+                self.cache_write.unwrap_or(self.input),
+                tokens.cache_creation,
+            )
+            + per_token(self.cache_read.unwrap_or(self.input), tokens.cache_read)
+    }
+}
+
+/// List prices at the time of writing, matched by the longest prefix of the
+/// model name. `prices` in the config overrides or extends these.
+const DEFAULT_PRICES: &[(&str, ModelPrice)] = &[
+    ("claude-opus-4-5", ModelPrice::new(5.0, 25.0, 6.25, 0.5)),
+    ("claude-opus-4", ModelPrice::new(15.0, 75.0, 18.75, 1.5)),
+    ("claude-sonnet-4", ModelPrice::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-7-sonnet", ModelPrice::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-3-5-sonnet", ModelPrice::new(3.0, 15.0, 3.75, 0.3)),
+    ("claude-haiku-4-5", ModelPrice::new(1.0, 5.0, 1.25, 0.1)),
+    ("claude-3-5-haiku", ModelPrice::new(0.8, 4.0, 1.0, 0.08)),
+    ("gpt-5", ModelPrice::new(1.25, 10.0, 1.25, 0.125)),
+    ("gemini-2.5-pro", ModelPrice::new(1.25, 10.0, 1.25, 0.31)),
+    ("gemini-2.5-flash", ModelPrice::new(0.3, 2.5, 0.3, 0.075)),
+];
+
+/// Only branches with a session modified within this window are shown in the
+/// unfiltered tree. The TUI cycles through the presets.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum RecentWindow {
+    Today, // the last 24 hours
+    #[default]
+    Week,
+    Month,
+    All,
+    Custom(Duration),
+}
+
+impl RecentWindow {
+    const PRESETS: [RecentWindow; 4] = [
+        RecentWindow::Today,
+        RecentWindow::Week,
+        RecentWindow::Month,
+        RecentWindow::All,
+    ];
+
+    pub fn duration(&self) -> Option<Duration> {
+        const DAY: u64 = 24 * 60 * 60;
+        match self {
+            RecentWindow::Today => Some(Duration::from_secs(DAY)),
+            RecentWindow::Week => Some(Duration::from_secs(7 * DAY)),
+            RecentWindow::Month => Some(Duration::from_secs(30 * DAY)),
+            RecentWindow::All => None,
+            RecentWindow::Custom(duration) => Some(*duration),
+        }
+    }
+
+    /// Oldest `modified` timestamp (ms since epoch) inside the window, or
+    /// `None` if everything is shown
+    pub fn cutoff_ms(&self) -> Option<i64> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        self.duration()
+            .map(|window| now.saturating_sub(window).as_millis() as i64)
+    }
+
+    /// The next preset, from shortest to `All` and back; a custom window
+    /// moves to the first preset longer than it
+    pub fn next(&self) -> RecentWindow {
+        match self {
+            RecentWindow::Custom(duration) => Self::PRESETS
+                .into_iter()
+                .find(|p| p.duration().is_none_or(|d| d > *duration))
+                .unwrap_or(RecentWindow::All),
+            _ => {
+                let idx = Self::PRESETS.iter().position(|p| p == self).unwrap_or(0);
+                Self::PRESETS[(idx + 1) % Self::PRESETS.len()]
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            RecentWindow::Today => "today".to_string(),
+            RecentWindow::Week => "week".to_string(),
+            RecentWindow::Month => "month".to_string(),
+            RecentWindow::All => "all".to_string(),
+            RecentWindow::Custom(duration) => format_duration(*duration),
+        }
+    }
+}
+
+impl TryFrom<String> for RecentWindow {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.trim() {
+            "today" => Ok(RecentWindow::Today),
+            "week" => Ok(RecentWindow::Week),
+            "month" => Ok(RecentWindow::Month),
+            "all" => Ok(RecentWindow::All),
+            other => parse_duration(other).map(RecentWindow::Custom),
+        }
+    }
+}
+
+/// A directory to search for git repos.
+///
+/// Either a plain path (searched one level deep) or a table with an explicit
+/// `max_depth`, e.g. `{ path = "~/code", max_depth = 2 }` for `~/code/<org>/<repo>`.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ScanDir {
+    Path(String),
+    Root {
+        path: String,
+        #[serde(default = "default_max_depth")]
+        max_depth: usize,
+    },
+}
+
+impl ScanDir {
+    pub fn path(&self) -> &str {
+        match self {
+            ScanDir::Path(path) => path,
+            ScanDir::Root { path, .. } => path,
+        }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        match self {
+            ScanDir::Path(_) => default_max_depth(),
+            ScanDir::Root { max_depth, .. } => *max_depth,
+        }
+    }
+}
+
+fn default_max_depth() -> usize {
+    1
 }
 
 fn default_scan_on_open() -> bool {
     true
 }
 
-fn default_scan_dirs() -> Vec<String> {
-    vec!["~/Documents".to_string()]
+fn default_scan_dirs() -> Vec<ScanDir> {
+    vec![ScanDir::Path("~/Documents".to_string())]
 }
 
 fn default_editor() -> String {
@@ -33,6 +288,11 @@ impl Default for Config {
             scan_dirs: default_scan_dirs(),
             editor: default_editor(),
             scan_on_open: default_scan_on_open(),
+            recent_window: RecentWindow::default(),
+            session_columns: Vec::new(),
+            prices: HashMap::new(),
+            providers: Vec::new(),
+            homes: HashMap::new(),
         }
     }
 }
@@ -44,6 +304,7 @@ impl Config {
         if config_path.exists() {
             let contents = fs::read_to_string(&config_path)?;
             let config: Config = toml::from_str(&contents)?;
+            config.validate()?;
             Ok(config)
         } else {
             Ok(Config::default())
@@ -65,12 +326,189 @@ impl Config {
         Ok(config_dir.join("config.toml"))
     }
 
-    pub fn expand_path(path: &str) -> PathBuf {
-        if path.starts_with("~/") {
-            if let Some(home) = dirs::home_dir() {
-                return home.join(&path[2..]);
+    /// Check what deserializing can't: provider names are unique, their
+    /// fields are JSON pointers, and homes are for agents that have one
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        for name in self.homes.keys() {
+            if ![claude::NAME, codex::NAME].contains(&name.as_str()) {
+                return Err(format!(
+                    "homes: '{}' has no agent home (expected {} or {})",
+                    name,
+                    claude::NAME,
+                    codex::NAME
+                )
+                .into());
             }
         }
+
+        let builtin = Registry::new(&Config::default());
+        let mut names = HashSet::new();
+        for provider in &self.providers {
+            if builtin.get(&provider.name).is_some() {
+                return Err(
+                    format!("providers: '{}' is a built-in provider", provider.name).into(),
+                );
+            }
+            if !names.insert(provider.name.as_str()) {
+                return Err(format!("providers: '{}' is declared twice", provider.name).into());
+            }
+            glob::Pattern::new(&provider.sessions).map_err(|e| {
+                format!(
+                    "providers: invalid sessions glob '{}' in '{}': {}",
+                    provider.sessions, provider.name, e
+                )
+            })?;
+            let fields = &provider.fields;
+            for pointer in [
+                &fields.id,
+                &fields.cwd,
+                &fields.branch,
+                &fields.prompt,
+                &fields.role,
+                &fields.timestamp,
+                &fields.messages,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(format!(
+                        "providers: '{}' in '{}' is not a JSON pointer (expected e.g. /{})",
+                        pointer, provider.name, pointer
+                    )
+                    .into());
+                }
+            }
+            if let Some(color) = &provider.color {
+                color.parse::<Color>().map_err(|_| {
+                    format!(
+                        "providers: invalid color '{}' in '{}'",
+                        color, provider.name
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Homes configured for a provider on top of its usual one
+    pub fn extra_homes(&self, provider: &str) -> &[String] {
+        self.homes.get(provider).map_or(&[], Vec::as_slice)
+    }
+
+    /// Price of `model`: the configured or built-in entry with the longest
+    /// matching prefix, configured ones winning ties
+    pub fn price(&self, model: &str) -> Option<ModelPrice> {
+        let configured = self
+            .prices
+            .iter()
+            .map(|(prefix, price)| (prefix.as_str(), *price));
+        let builtin = DEFAULT_PRICES
+            .iter()
+            .map(|(prefix, price)| (*prefix, *price));
+        configured
+            .chain(builtin)
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .fold(None, |best: Option<(&str, ModelPrice)>, entry| match best {
+                Some(best) if best.0.len() >= entry.0.len() => Some(best),
+                _ => Some(entry),
+            })
+            .map(|(_, price)| price)
+    }
+
+    /// Estimated cost in USD of a model's tokens, if the model has a price
+    pub fn cost(&self, model: &str, tokens: &TokenUsage) -> Option<f64> {
+        self.price(model).map(|price| price.cost(tokens))
+    }
+
+    pub fn expand_path(path: &str) -> PathBuf {
+        if path.starts_with("~/")
+            && let Some(home) = dirs::home_dir()
+        {
+            return home.join(&path[2..]);
+        }
         PathBuf::from(path)
     }
 }
+
+/// Format a duration in the largest unit `parse_duration` accepts that divides it
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    for (unit, size) in [
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ] {
+        if secs > 0 && secs.is_multiple_of(size) {
+            return format!("{}{}", secs / size, unit);
+        }
+    }
+    format!("{}s", secs)
+}
+
+/// Parse a duration like `30s`, `15m`, `12h`, `14d` or `2w`
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}': expected e.g. 14d", s))?;
+
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid duration '{}': unit must be one of s, m, h, d, w",
+                s
+            ));
+        }
+    };
+    Ok(Duration::from_secs(amount * secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_unit() {
+        for (input, secs) in [
+            ("30s", 30),
+            ("15m", 15 * 60),
+            ("12h", 12 * 3600),
+            ("14d", 14 * 86400),
+            ("2w", 14 * 86400),
+            (" 0d ", 0),
+        ] {
+            assert_eq!(
+                parse_duration(input),
+                Ok(Duration::from_secs(secs)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_durations() {
+        for input in ["", "d", "14", "14 d", "1.5h", "-1d", "3y", "14D"] {
+            assert!(parse_duration(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn format_round_trips() {
+        for input in ["45s", "90m", "36h", "13d", "2w"] {
+            let duration = parse_duration(input).unwrap();
+            assert_eq!(format_duration(duration), input);
+        }
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(0)), "0s");
+    }
+}
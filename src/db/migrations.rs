@@ -0,0 +1,443 @@
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::error::Error;
+
+/// A numbered schema change. Applied in order; `PRAGMA user_version` records
+/// the last one applied.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+/// All migrations, oldest first. Append new ones at the end; never edit or
+/// reorder a migration once it has shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create repos, worktrees and sessions tables",
+        apply: create_base_tables,
+    },
+    Migration {
+        version: 2,
+        description: "record scan durations",
+        apply: create_scans,
+    },
+    Migration {
+        version: 3,
+        description: "add session file checkpoints",
+        apply: add_session_checkpoints,
+    },
+    Migration {
+        version: 4,
+        description: "link sessions to repos and worktrees",
+        apply: add_session_links,
+    },
+    Migration {
+        version: 5,
+        description: "archive missing repos and sessions instead of deleting them",
+        apply: add_missing_since,
+    },
+    Migration {
+        version: 6,
+        description: "index sessions for tree queries",
+        apply: add_session_indexes,
+    },
+    Migration {
+        version: 7,
+        description: "full-text index over session transcripts",
+        apply: create_session_text,
+    },
+    Migration {
+        version: 8,
+        description: "token usage per reply, with the time it was written",
+        apply: create_session_usage,
+    },
+    Migration {
+        version: 9,
+        description: "files written by each session",
+        apply: create_session_files,
+    },
+    Migration {
+        version: 10,
+        description: "session lineage across resumed, compacted and sidechain sessions",
+        apply: create_session_lineage,
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Migrations not yet applied to `conn`.
+///
+/// Fails if the database was written by a newer ws, whose schema this
+/// binary doesn't understand.
+pub fn pending(conn: &Connection) -> Result<Vec<&'static Migration>, Box<dyn Error>> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(format!(
+            "database schema version {} is newer than this ws supports ({}); upgrade ws",
+            current, latest
+        )
+        .into());
+    }
+
+    Ok(MIGRATIONS.iter().filter(|m| m.version > current).collect())
+}
+
+/// Apply all pending migrations, each in its own transaction, and return
+/// the ones this call applied.
+///
+/// Each step takes the write lock up front and re-reads the schema version
+/// under it, so two processes opening an old database at once don't both
+/// apply the same step.
+pub fn run(conn: &mut Connection) -> Result<Vec<&'static Migration>, Box<dyn Error>> {
+    let mut applied = Vec::new();
+
+    for migration in pending(conn)? {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let current: u32 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if current >= migration.version {
+            continue;
+        }
+        (migration.apply)(&tx).map_err(|e| {
+            format!(
+                "migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            )
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+/// Add a column unless it already exists. Databases created before versioned
+/// migrations may have any subset of the columns added by early migrations.
+fn add_column(tx: &Transaction, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let exists = tx
+        .prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table))
+        .is_ok();
+    if !exists {
+        tx.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+fn create_base_tables(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS repos (
+            id INTEGER PRIMARY KEY,
+            path TEXT UNIQUE NOT NULL,
+            name TEXT NOT NULL,
+            last_scanned INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS worktrees (
+            id INTEGER PRIMARY KEY,
+            repo_id INTEGER NOT NULL REFERENCES repos(id) ON DELETE CASCADE,
+            path TEXT UNIQUE NOT NULL,
+            branch TEXT,
+            UNIQUE(repo_id, path)
+        );
+
+        CREATE TABLE IF NOT EXISTS sessions (
+            uuid TEXT PRIMARY KEY,
+            project_path TEXT NOT NULL,
+            git_branch TEXT,
+            summary TEXT,
+            first_prompt TEXT,
+            modified INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_branch ON sessions(git_branch);
+        CREATE INDEX IF NOT EXISTS idx_worktrees_branch ON worktrees(branch);
+        "#,
+    )?;
+
+    add_column(tx, "sessions", "message_count", "INTEGER")?;
+    add_column(tx, "sessions", "provider", "TEXT NOT NULL DEFAULT 'claude'")
+}
+
+fn create_scans(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS scans (
+            id INTEGER PRIMARY KEY,
+            started_at INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            repo_count INTEGER NOT NULL,
+            session_count INTEGER NOT NULL
+        );
+        "#,
+    )
+}
+
+fn add_session_checkpoints(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column(tx, "sessions", "file_path", "TEXT")?;
+    add_column(tx, "sessions", "file_size", "INTEGER")?;
+    // NULL until the file is next parsed; until then `modified` is its mtime
+    add_column(tx, "sessions", "file_modified", "INTEGER")?;
+    add_column(tx, "sessions", "parsed_offset", "INTEGER")?;
+    // The agent home the file was found under, for agents that can have several
+    add_column(tx, "sessions", "agent_home", "TEXT")
+}
+
+fn add_session_links(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column(tx, "repos", "git_common_dir", "TEXT")?;
+    add_column(tx, "sessions", "repo_id", "INTEGER")?;
+    add_column(tx, "sessions", "worktree_id", "INTEGER")?;
+    add_column(tx, "sessions", "git_common_dir", "TEXT")?;
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_sessions_repo ON sessions(repo_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_missing_since(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column(tx, "repos", "missing_since", "INTEGER")?;
+    add_column(tx, "sessions", "missing_since", "INTEGER")
+}
+
+fn add_session_indexes(tx: &Transaction) -> rusqlite::Result<()> {
+    // "(no branch)" is stored as NULL only, so branches can be looked up by index
+    tx.execute_batch(
+        r#"
+        UPDATE sessions SET git_branch = NULL WHERE git_branch = '';
+
+        CREATE INDEX IF NOT EXISTS idx_sessions_project_modified ON sessions(project_path, modified);
+        CREATE INDEX IF NOT EXISTS idx_sessions_branch ON sessions(git_branch);
+        CREATE INDEX IF NOT EXISTS idx_sessions_repo_branch
+            ON sessions(repo_id, git_branch, modified, missing_since);
+        "#,
+    )
+}
+
+fn create_session_text(tx: &Transaction) -> rusqlite::Result<()> {
+    // session_text holds the rows; the external-content FTS table indexes them
+    // and is kept in sync by triggers
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS session_text (
+            id INTEGER PRIMARY KEY,
+            uuid TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            content TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_session_text_uuid ON session_text(uuid);
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS session_text_fts USING fts5(
+            content,
+            content = 'session_text',
+            content_rowid = 'id',
+            tokenize = 'porter unicode61'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS session_text_insert AFTER INSERT ON session_text BEGIN
+            INSERT INTO session_text_fts (rowid, content) VALUES (new.id, new.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS session_text_delete AFTER DELETE ON session_text BEGIN
+            INSERT INTO session_text_fts (session_text_fts, rowid, content)
+            VALUES ('delete', old.id, old.content);
+        END;
+        "#,
+    )
+}
+
+fn create_session_usage(tx: &Transaction) -> rusqlite::Result<()> {
+    // Replies are keyed by id so one read again isn't counted twice
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS session_usage (
+            uuid TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            model TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            input_tokens INTEGER NOT NULL,
+            output_tokens INTEGER NOT NULL,
+            cache_creation_tokens INTEGER NOT NULL,
+            cache_read_tokens INTEGER NOT NULL,
+            PRIMARY KEY (uuid, message_id)
+        ) WITHOUT ROWID;
+        "#,
+    )
+}
+
+fn create_session_files(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS session_files (
+            uuid TEXT NOT NULL,
+            path TEXT NOT NULL,
+            PRIMARY KEY (uuid, path)
+        );
+        "#,
+    )
+}
+
+fn create_session_lineage(tx: &Transaction) -> rusqlite::Result<()> {
+    // session_messages (the boundary messages a later session can continue
+    // from) and session_links are what the scanner found; session_lineage is
+    // resolved from them after each scan
+    tx.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS session_messages (
+            message_uuid TEXT NOT NULL,
+            uuid TEXT NOT NULL,
+            PRIMARY KEY (message_uuid, uuid)
+        ) WITHOUT ROWID;
+        CREATE INDEX IF NOT EXISTS idx_session_messages_uuid ON session_messages(uuid);
+
+        CREATE TABLE IF NOT EXISTS session_links (
+            uuid TEXT NOT NULL,
+            target TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            PRIMARY KEY (uuid, target)
+        ) WITHOUT ROWID;
+
+        CREATE TABLE IF NOT EXISTS session_lineage (
+            uuid TEXT PRIMARY KEY,
+            parent_uuid TEXT NOT NULL,
+            kind TEXT NOT NULL
+        );
+        "#,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(conn: &Connection) -> u32 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    fn versions(applied: &[&Migration]) -> Vec<u32> {
+        applied.iter().map(|m| m.version).collect()
+    }
+
+    /// A database as an older ws left it, at `version`
+    fn database_at(version: u32) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+            let tx = conn.transaction().unwrap();
+            (migration.apply)(&tx).unwrap();
+            tx.pragma_update(None, "user_version", migration.version)
+                .unwrap();
+            tx.commit().unwrap();
+        }
+        conn
+    }
+
+    fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+        conn.prepare(&format!("SELECT {} FROM {} LIMIT 0", column, table))
+            .is_ok()
+    }
+
+    #[test]
+    fn versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn creates_a_new_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let applied = run(&mut conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(version(&conn), latest_version());
+        assert!(run(&mut conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn upgrades_a_database_from_before_versioning() {
+        // Unversioned, with some of the columns early migrations add
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE repos (id INTEGER PRIMARY KEY, path TEXT UNIQUE NOT NULL,
+                name TEXT NOT NULL, last_scanned INTEGER NOT NULL, git_common_dir TEXT);
+             CREATE TABLE sessions (uuid TEXT PRIMARY KEY, project_path TEXT NOT NULL,
+                git_branch TEXT, summary TEXT, first_prompt TEXT, modified INTEGER NOT NULL,
+                provider TEXT NOT NULL DEFAULT 'claude');
+             INSERT INTO sessions (uuid, project_path, git_branch, modified)
+                VALUES ('s1', '/code/api', '', 1);",
+        )
+        .unwrap();
+
+        let applied = run(&mut conn).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(version(&conn), latest_version());
+        for column in ["message_count", "file_size", "repo_id", "agent_home"] {
+            assert!(has_column(&conn, "sessions", column), "{}", column);
+        }
+        assert!(has_column(&conn, "repos", "missing_since"));
+        let (branch, file_path): (Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT git_branch, file_path FROM sessions WHERE uuid = 's1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(branch, None);
+        assert_eq!(file_path, None);
+    }
+
+    #[test]
+    fn applies_only_the_missing_steps() {
+        let mut conn = database_at(5);
+        conn.execute_batch(
+            "INSERT INTO sessions (uuid, project_path, git_branch, modified, provider, file_path, file_size)
+                VALUES ('s1', '/code/api', 'main', 1, 'codex', '/s1.jsonl', 10);",
+        )
+        .unwrap();
+
+        let applied = run(&mut conn).unwrap();
+        assert_eq!(
+            versions(&applied),
+            (6..=latest_version()).collect::<Vec<_>>()
+        );
+        assert_eq!(version(&conn), latest_version());
+        let (branch, file_path, home): (String, Option<String>, Option<String>) = conn
+            .query_row(
+                "SELECT git_branch, file_path, agent_home FROM sessions WHERE uuid = 's1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(branch, "main");
+        assert_eq!(file_path.as_deref(), Some("/s1.jsonl"));
+        assert_eq!(home, None);
+    }
+
+    #[test]
+    fn leaves_a_shared_database_to_the_first_to_migrate() {
+        let path = std::env::temp_dir().join(format!("ws-migrations-{}.db", std::process::id()));
+        let mut first = Connection::open(&path).unwrap();
+        let mut second = Connection::open(&path).unwrap();
+
+        assert_eq!(run(&mut first).unwrap().len(), MIGRATIONS.len());
+        assert!(run(&mut second).unwrap().is_empty());
+        drop((first, second));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_a_newer_database() {
+        let mut conn = database_at(latest_version());
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        assert!(pending(&conn).is_err());
+        assert!(run(&mut conn).is_err());
+    }
+}
@@ -0,0 +1,1862 @@
+mod migrations;
+
+pub use migrations::Migration;
+
+use crate::query::Query;
+use crate::scanner::{
+    claude::{FileCheckpoint, LinkKind, Session, TokenUsage},
+    git::Repo,
+};
+use rusqlite::types::Value;
+use rusqlite::{Connection, params, params_from_iter};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fs::{File, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub struct Database {
+    conn: Connection,
+}
+
+/// `sessions.git_common_dir` of a session whose cwd isn't in any repo
+const NO_REPO: &str = "";
+
+/// Worktree and repo rows as `link_sessions` matches cwds against them
+pub struct RepoLayout {
+    /// (id, repo_id, path), longest path first
+    worktrees: Vec<(i64, i64, PathBuf)>,
+    /// (id, path, git_common_dir)
+    repos: Vec<(i64, PathBuf, Option<String>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RepoData {
+    pub path: PathBuf,
+    pub name: String,
+    pub worktrees: Vec<WorktreeInfo>, // All worktrees in repo
+    pub branches: Vec<BranchData>,
+    pub missing_since: Option<i64>, // set while archived (seconds since epoch)
+}
+
+#[derive(Debug, Clone)]
+pub struct WorktreeInfo {
+    pub path: PathBuf,
+    pub name: String,                       // folder name for display
+    pub checked_out_branch: Option<String>, // which branch is checked out
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchData {
+    pub branch: String,
+    pub sessions: Vec<SessionData>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionData {
+    pub uuid: String,
+    pub project_path: String,
+    pub summary: Option<String>,
+    pub first_prompt: Option<String>,
+    pub modified: i64,
+    pub message_count: Option<i64>,
+    pub provider: String,
+    pub missing_since: Option<i64>, // set while archived (seconds since epoch)
+    pub snippet: Option<String>,    // matched text when found by text search
+    pub file_path: Option<PathBuf>, // transcript file, once a scan has recorded it
+    pub home: Option<PathBuf>,      // agent home it was found under, if the agent has several
+    pub usage: Vec<ModelUsage>,     // tokens per model, most output first
+    pub link: Option<LinkKind>,     // how it continues its parent in the lineage
+    pub lineage: Vec<SessionData>,  // on a chain's newest leaf: the chain's other sessions
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelUsage {
+    pub model: String,
+    pub tokens: TokenUsage,
+}
+
+/// One session's usage of one model, as grouped by `Database::get_usage`
+#[derive(Debug, Clone)]
+pub struct UsageRow {
+    pub group: String,
+    pub uuid: String,
+    pub model: String,
+    pub tokens: TokenUsage,
+}
+
+/// How `Database::get_usage` groups sessions
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum UsageGroup {
+    Repo,
+    /// Repo and branch
+    Branch,
+    /// Local date of each reply
+    Day,
+}
+
+/// A session matched by `Database::search_sessions`, best match first
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub session: SessionData,
+    pub branch: String,
+    pub repo_id: Option<i64>,
+    pub repo_name: Option<String>,
+    pub repo_path: Option<PathBuf>,
+    pub repo_missing_since: Option<i64>,
+}
+
+/// Branch name shown for sessions recorded outside any branch
+pub const NO_BRANCH: &str = "(no branch)";
+
+/// Snippets mark matched terms with these control characters
+pub const MATCH_START: char = '\u{1}';
+pub const MATCH_END: char = '\u{2}';
+
+/// Summary of a completed scan, recorded in the `scans` table
+#[derive(Debug, Clone)]
+pub struct ScanStats {
+    pub started_at: i64, // seconds since epoch
+    pub duration_ms: i64,
+    pub repo_count: i64,
+    pub session_count: i64,
+    /// Problems that didn't stop the scan; not recorded
+    pub warnings: Vec<String>,
+}
+
+/// Rows removed by `Database::purge_archived`
+#[derive(Debug, Clone, Default)]
+pub struct PurgeStats {
+    pub repos: usize,
+    pub sessions: usize,
+}
+
+/// Held for the duration of a scan; the file lock is released on drop
+/// (or when the process exits, so a crashed scan never leaves it stuck)
+pub struct ScanLock {
+    _file: File,
+}
+
+impl Database {
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        let db_path = Self::db_path()?;
+
+        // Ensure directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut conn = Self::connect(&db_path)?;
+        migrations::run(&mut conn)?;
+        Ok(Database { conn })
+    }
+
+    fn connect(db_path: &Path) -> Result<Connection, Box<dyn Error>> {
+        let conn = Connection::open(db_path)?;
+        // WAL lets the TUI keep reading while a scan writes; the timeout makes
+        // a second writer wait for the lock instead of failing immediately
+        conn.busy_timeout(Duration::from_secs(10))?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Ok(conn)
+    }
+
+    /// Take the advisory scan lock, so overlapping scans (TUI, cron) don't
+    /// interleave writes. With `wait`, blocks until the other scan finishes;
+    /// otherwise returns `None` if one is already running.
+    pub fn lock_scan(wait: bool) -> Result<Option<ScanLock>, Box<dyn Error>> {
+        let lock_path = Self::db_path()?.with_file_name("scan.lock");
+        if let Some(parent) = lock_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&lock_path)?;
+
+        if wait {
+            file.lock()?;
+        } else {
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Ok(None),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+
+        Ok(Some(ScanLock { _file: file }))
+    }
+
+    /// Run `f` as a single transaction: committed if it succeeds, rolled back
+    /// otherwise, so readers never observe a partially applied scan
+    pub fn in_transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        self.conn.execute_batch("BEGIN IMMEDIATE")?;
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    /// Migrations `open` would apply, without applying them
+    pub fn pending_migrations() -> Result<Vec<&'static Migration>, Box<dyn Error>> {
+        let db_path = Self::db_path()?;
+        if !db_path.exists() {
+            return Ok(migrations::MIGRATIONS.iter().collect());
+        }
+        let conn = Self::connect(&db_path)?;
+        migrations::pending(&conn)
+    }
+
+    fn db_path() -> Result<PathBuf, Box<dyn Error>> {
+        let config_dir = dirs::config_dir()
+            .ok_or("Could not find config directory")?
+            .join("ws");
+        Ok(config_dir.join("ws.db"))
+    }
+
+    pub fn upsert_repo(&mut self, repo: &Repo) -> Result<(), Box<dyn Error>> {
+        let now = unix_now()?;
+
+        self.conn.execute(
+            "INSERT INTO repos (path, name, last_scanned, git_common_dir)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(path) DO UPDATE SET
+                name = excluded.name,
+                last_scanned = excluded.last_scanned,
+                git_common_dir = excluded.git_common_dir,
+                missing_since = NULL",
+            params![
+                repo.path.to_string_lossy(),
+                repo.name,
+                now,
+                repo.git_common_dir
+                    .as_ref()
+                    .map(|d| d.to_string_lossy().to_string())
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn upsert_worktree(
+        &mut self,
+        repo_path: &Path,
+        worktree: &crate::scanner::git::Worktree,
+    ) -> Result<(), Box<dyn Error>> {
+        // Get repo_id from repo path
+        let repo_id: i64 = self.conn.query_row(
+            "SELECT id FROM repos WHERE path = ?1",
+            params![repo_path.to_string_lossy()],
+            |row| row.get(0),
+        )?;
+
+        self.conn.execute(
+            "INSERT INTO worktrees (repo_id, path, branch)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(path) DO UPDATE SET
+                repo_id = excluded.repo_id,
+                branch = excluded.branch",
+            params![repo_id, worktree.path.to_string_lossy(), worktree.branch],
+        )?;
+        Ok(())
+    }
+
+    pub fn upsert_session(&mut self, session: &Session) -> Result<(), Box<dyn Error>> {
+        let checkpoint = session.checkpoint.as_ref();
+        self.conn.execute(
+            "INSERT INTO sessions (uuid, project_path, git_branch, summary, first_prompt, modified, message_count, provider,
+                                   agent_home, file_path, file_size, file_modified, parsed_offset)
+             VALUES (?1, ?2, NULLIF(?3, ''), ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(uuid) DO UPDATE SET
+                project_path = excluded.project_path,
+                git_branch = excluded.git_branch,
+                summary = excluded.summary,
+                first_prompt = excluded.first_prompt,
+                modified = excluded.modified,
+                message_count = excluded.message_count,
+                provider = excluded.provider,
+                agent_home = excluded.agent_home,
+                file_path = excluded.file_path,
+                file_size = excluded.file_size,
+                file_modified = excluded.file_modified,
+                parsed_offset = excluded.parsed_offset,
+                missing_since = NULL",
+            params![
+                session.uuid,
+                session.project_path,
+                session.git_branch,
+                session.summary,
+                session.first_prompt,
+                session.modified,
+                session.message_count,
+                session.provider,
+                session
+                    .home
+                    .as_ref()
+                    .map(|home| home.to_string_lossy().to_string()),
+                checkpoint.map(|cp| cp.path.to_string_lossy().to_string()),
+                checkpoint.map(|cp| cp.size),
+                checkpoint.map(|cp| cp.modified),
+                checkpoint.map(|cp| cp.offset),
+            ],
+        )?;
+
+        if !session.transcript_resumed {
+            self.conn.execute(
+                "DELETE FROM session_text WHERE uuid = ?1",
+                params![session.uuid],
+            )?;
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached("INSERT INTO session_text (uuid, kind, content) VALUES (?1, ?2, ?3)")?;
+        for text in &session.transcript {
+            stmt.execute(params![session.uuid, text.kind.as_str(), text.text])?;
+        }
+        drop(stmt);
+
+        if !session.transcript_resumed {
+            self.conn.execute(
+                "DELETE FROM session_usage WHERE uuid = ?1",
+                params![session.uuid],
+            )?;
+        }
+        // A reply read again replaces its earlier count
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR REPLACE INTO session_usage (uuid, message_id, model, timestamp, input_tokens,
+                                                   output_tokens, cache_creation_tokens, cache_read_tokens)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+        for usage in &session.usage {
+            stmt.execute(params![
+                session.uuid,
+                usage.id,
+                usage.model,
+                usage.timestamp,
+                usage.tokens.input,
+                usage.tokens.output,
+                usage.tokens.cache_creation,
+                usage.tokens.cache_read
+            ])?;
+        }
+        drop(stmt);
+
+        if !session.transcript_resumed {
+            self.conn.execute(
+                "DELETE FROM session_files WHERE uuid = ?1",
+                params![session.uuid],
+            )?;
+        }
+        let mut stmt = self
+            .conn
+            .prepare_cached("INSERT OR IGNORE INTO session_files (uuid, path) VALUES (?1, ?2)")?;
+        for path in &session.files {
+            stmt.execute(params![session.uuid, path])?;
+        }
+        drop(stmt);
+
+        if !session.transcript_resumed {
+            for table in ["session_messages", "session_links"] {
+                self.conn.execute(
+                    &format!("DELETE FROM {} WHERE uuid = ?1", table),
+                    params![session.uuid],
+                )?;
+            }
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO session_messages (message_uuid, uuid) VALUES (?1, ?2)",
+        )?;
+        for id in &session.boundary_ids {
+            stmt.execute(params![id, session.uuid])?;
+        }
+        let mut stmt = self.conn.prepare_cached(
+            "INSERT OR IGNORE INTO session_links (uuid, target, kind) VALUES (?1, ?2, ?3)",
+        )?;
+        for link in &session.links {
+            stmt.execute(params![session.uuid, link.target, link.kind.as_str()])?;
+        }
+        Ok(())
+    }
+
+    /// Files a session wrote, by path
+    pub fn get_session_files(&self, uuid: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path FROM session_files WHERE uuid = ?1 ORDER BY path")?;
+        let files = stmt
+            .query_map(params![uuid], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
+    /// Load previously parsed sessions grouped by their file path, used to
+    /// skip unchanged session files or incrementally re-parse grown ones
+    pub fn get_session_checkpoints(
+        &self,
+    ) -> Result<HashMap<PathBuf, Vec<Session>>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT uuid, project_path, git_branch, summary, first_prompt, modified, message_count,
+                    file_path, file_size, parsed_offset, provider, COALESCE(file_modified, modified),
+                    agent_home
+             FROM sessions
+             WHERE file_path IS NOT NULL",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                let path = PathBuf::from(row.get::<_, String>(7)?);
+                let session = Session {
+                    uuid: row.get(0)?,
+                    project_path: row.get(1)?,
+                    git_branch: row.get(2)?,
+                    summary: row.get(3)?,
+                    first_prompt: row.get(4)?,
+                    modified: row.get(5)?,
+                    message_count: row.get(6)?,
+                    provider: row.get(10)?,
+                    home: row.get::<_, Option<String>>(12)?.map(PathBuf::from),
+                    checkpoint: Some(FileCheckpoint {
+                        path: path.clone(),
+                        size: row.get(8)?,
+                        modified: row.get(11)?,
+                        offset: row.get(9)?,
+                    }),
+                    transcript: Vec::new(),
+                    usage: Vec::new(),
+                    files: BTreeSet::new(),
+                    boundary_ids: Vec::new(),
+                    links: Vec::new(),
+                    transcript_resumed: false,
+                };
+                Ok((path, session))
+            })?
+            .filter_map(Result::ok);
+
+        let mut checkpoints: HashMap<PathBuf, Vec<Session>> = HashMap::new();
+        for (path, session) in rows {
+            checkpoints.entry(path).or_default().push(session);
+        }
+        Ok(checkpoints)
+    }
+
+    /// Archive repos not in `current_paths` (those this scan found or failed
+    /// to read), and unarchive those that are back. Archived repos keep their worktrees and sessions, so a
+    /// temporarily unmounted drive or misconfigured scan dir loses nothing.
+    pub fn archive_stale_repos(
+        &mut self,
+        current_paths: &HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.conn.prepare("SELECT path, missing_since FROM repos")?;
+        let all_repos: Vec<(String, Option<i64>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(Result::ok)
+            .collect();
+        drop(stmt);
+
+        let now = unix_now()?;
+        for (path, missing_since) in all_repos {
+            let missing_since = match (current_paths.contains(&path), missing_since) {
+                (true, Some(_)) => None,
+                (false, None) => Some(now),
+                _ => continue,
+            };
+            self.conn.execute(
+                "UPDATE repos SET missing_since = ?1 WHERE path = ?2",
+                params![missing_since, path],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete worktrees that were removed from repos found by this scan.
+    /// Worktrees of archived repos are kept along with the repo.
+    pub fn delete_stale_worktrees(&mut self, current_repos: &[Repo]) -> Result<(), Box<dyn Error>> {
+        let current_repo_paths: HashSet<_> = current_repos
+            .iter()
+            .map(|r| r.path.to_string_lossy().to_string())
+            .collect();
+        let current_paths: HashSet<_> = current_repos
+            .iter()
+            .flat_map(|r| r.worktrees.iter())
+            .map(|wt| wt.path.to_string_lossy().to_string())
+            .collect();
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT w.path, r.path FROM worktrees w JOIN repos r ON r.id = w.repo_id")?;
+        let all_paths: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(Result::ok)
+            .collect();
+        drop(stmt);
+
+        for (path, repo_path) in all_paths {
+            if current_repo_paths.contains(&repo_path) && !current_paths.contains(&path) {
+                self.conn
+                    .execute("DELETE FROM worktrees WHERE path = ?1", params![path])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The worktree and repo rows sessions are linked against, to be passed
+    /// to `link_sessions` after the scan has written its own
+    pub fn repo_layout(&self) -> Result<RepoLayout, Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, repo_id, path FROM worktrees")?;
+        let mut worktrees: Vec<(i64, i64, PathBuf)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    PathBuf::from(row.get::<_, String>(2)?),
+                ))
+            })?
+            .filter_map(Result::ok)
+            .collect();
+        drop(stmt);
+        // Longest path first, so nested worktrees win over their parents
+        worktrees.sort_by_key(|(_, _, path)| std::cmp::Reverse(path.components().count()));
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, path, git_common_dir FROM repos")?;
+        let repos = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    PathBuf::from(row.get::<_, String>(1)?),
+                    row.get(2)?,
+                ))
+            })?
+            .filter_map(Result::ok)
+            .collect();
+
+        Ok(RepoLayout { worktrees, repos })
+    }
+
+    /// Link sessions to the worktree (and repo) their cwd is in.
+    ///
+    /// The cwd is canonicalized and matched against worktree paths at directory
+    /// boundaries, so subdirectories resolve to their enclosing worktree and
+    /// `api-gateway` is never mistaken for `api`. Each session remembers its repo's
+    /// git common dir, which keeps sessions from since-deleted worktrees attached
+    /// to their repo. `common_dir_of` resolves cwds that match no known worktree;
+    /// a cwd outside any repo is remembered as such and not resolved again.
+    ///
+    /// Only the `upserted` sessions and those a worktree or repo row changed
+    /// under since `before` are relinked. Issues one UPDATE per session, so
+    /// call it inside `in_transaction`.
+    pub fn link_sessions(
+        &mut self,
+        before: &RepoLayout,
+        upserted: &HashSet<String>,
+        mut common_dir_of: impl FnMut(&Path) -> Option<PathBuf>,
+    ) -> Result<(), Box<dyn Error>> {
+        let RepoLayout { worktrees, repos } = self.repo_layout()?;
+        let repo_common_dir = |repo_id: i64| {
+            repos
+                .iter()
+                .find(|(id, _, _)| *id == repo_id)
+                .and_then(|(_, _, dir)| dir.clone())
+        };
+
+        // Rows added, removed or changed by this scan
+        let changed_worktrees: Vec<&(i64, i64, PathBuf)> = worktrees
+            .iter()
+            .filter(|wt| !before.worktrees.contains(wt))
+            .chain(before.worktrees.iter().filter(|wt| !worktrees.contains(wt)))
+            .collect();
+        let changed_repos: Vec<&(i64, PathBuf, Option<String>)> = repos
+            .iter()
+            .filter(|repo| !before.repos.contains(repo))
+            .chain(before.repos.iter().filter(|repo| !repos.contains(repo)))
+            .collect();
+        let affected = |cwd: &Path,
+                        repo_id: Option<i64>,
+                        worktree_id: Option<i64>,
+                        common_dir: Option<&str>| {
+            changed_worktrees
+                .iter()
+                .any(|(id, _, path)| Some(*id) == worktree_id || cwd.starts_with(path))
+                || changed_repos.iter().any(|(id, path, dir)| {
+                    Some(*id) == repo_id
+                        || cwd.starts_with(path)
+                        || (dir.is_some() && dir.as_deref() == common_dir)
+                })
+        };
+        let layout_changed = !changed_worktrees.is_empty() || !changed_repos.is_empty();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT uuid, project_path, git_common_dir, repo_id, worktree_id FROM sessions",
+        )?;
+        #[allow(clippy::type_complexity)]
+        let sessions: Vec<(String, String, Option<String>, Option<i64>, Option<i64>)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            })?
+            .filter_map(Result::ok)
+            .collect();
+        drop(stmt);
+
+        let mut resolved: HashMap<PathBuf, Option<String>> = HashMap::new();
+        for (uuid, project_path, stored_common_dir, stored_repo_id, stored_worktree_id) in sessions
+        {
+            if project_path.is_empty() {
+                continue;
+            }
+            let is_upserted = upserted.contains(&uuid);
+            if !is_upserted && !layout_changed {
+                continue;
+            }
+            let cwd = Path::new(&project_path);
+            let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+            if !is_upserted
+                && !affected(
+                    &cwd,
+                    stored_repo_id,
+                    stored_worktree_id,
+                    stored_common_dir.as_deref(),
+                )
+            {
+                continue;
+            }
+
+            let (repo_id, worktree_id, common_dir) = if let Some((wt_id, repo_id, _)) =
+                worktrees.iter().find(|(_, _, path)| cwd.starts_with(path))
+            {
+                (Some(*repo_id), Some(*wt_id), repo_common_dir(*repo_id))
+            } else if let Some((repo_id, _, dir)) =
+                repos.iter().find(|(_, path, _)| cwd.starts_with(path))
+            {
+                (Some(*repo_id), None, dir.clone())
+            } else {
+                // Worktree unknown or gone: fall back to the repo's common dir,
+                // or to NO_REPO once the cwd is known not to be in one
+                let common_dir = stored_common_dir.unwrap_or_else(|| {
+                    resolved
+                        .entry(cwd.clone())
+                        .or_insert_with(|| {
+                            common_dir_of(&cwd).map(|d| d.to_string_lossy().to_string())
+                        })
+                        .clone()
+                        .unwrap_or_else(|| NO_REPO.to_string())
+                });
+                let repo_id = repos
+                    .iter()
+                    .find(|(_, _, repo_dir)| repo_dir.as_ref() == Some(&common_dir))
+                    .map(|(id, _, _)| *id);
+                (repo_id, None, Some(common_dir))
+            };
+
+            self.conn.execute(
+                "UPDATE sessions SET repo_id = ?1, worktree_id = ?2, git_common_dir = ?3 WHERE uuid = ?4",
+                params![repo_id, worktree_id, common_dir, uuid],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Update `session_lineage` from the links the scanner recorded, for the
+    /// `upserted` sessions and the sessions linking to them.
+    ///
+    /// A session's parent is the session holding the message its link points
+    /// to (or, for a sidechain, the session it names). Claude copies messages
+    /// between files, so a message can be in several sessions; the most
+    /// recently modified one that is older than the linking session wins,
+    /// which also keeps resume links from forming cycles.
+    pub fn link_lineage(&mut self, upserted: &HashSet<String>) -> Result<(), Box<dyn Error>> {
+        self.conn.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS lineage_changed (uuid TEXT PRIMARY KEY);
+             DELETE FROM lineage_changed;",
+        )?;
+        let mut stmt = self
+            .conn
+            .prepare_cached("INSERT OR IGNORE INTO lineage_changed (uuid) VALUES (?1)")?;
+        for uuid in upserted {
+            stmt.execute(params![uuid])?;
+        }
+        drop(stmt);
+
+        self.conn.execute_batch(
+            "INSERT OR IGNORE INTO lineage_changed (uuid)
+                SELECT l.uuid FROM session_links l
+                JOIN session_messages m ON m.message_uuid = l.target
+                WHERE m.uuid IN (SELECT uuid FROM lineage_changed)
+                UNION
+                SELECT uuid FROM session_links
+                WHERE kind = 'sidechain' AND target IN (SELECT uuid FROM lineage_changed)
+                UNION
+                SELECT uuid FROM session_lineage
+                WHERE parent_uuid IN (SELECT uuid FROM lineage_changed);
+             DELETE FROM session_lineage WHERE uuid IN (SELECT uuid FROM lineage_changed);
+             WITH candidates AS (
+                SELECT l.uuid, m.uuid AS parent_uuid, l.kind
+                FROM session_links l
+                JOIN session_messages m ON m.message_uuid = l.target AND m.uuid != l.uuid
+                WHERE l.kind != 'sidechain'
+                UNION ALL
+                SELECT uuid, target, kind FROM session_links
+                WHERE kind = 'sidechain' AND target != uuid
+             ),
+             ranked AS (
+                SELECT c.uuid, c.parent_uuid, c.kind,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY c.uuid
+                           ORDER BY c.kind = 'sidechain' DESC, p.modified DESC, p.uuid
+                       ) AS rank
+                FROM candidates c
+                JOIN sessions s ON s.uuid = c.uuid
+                JOIN sessions p ON p.uuid = c.parent_uuid
+                WHERE c.uuid IN (SELECT uuid FROM lineage_changed)
+                  AND (p.modified < s.modified OR c.kind = 'sidechain')
+             )
+             INSERT INTO session_lineage (uuid, parent_uuid, kind)
+             SELECT uuid, parent_uuid, kind FROM ranked WHERE rank = 1;",
+        )?;
+        Ok(())
+    }
+
+    /// Archive sessions whose files were not found by this scan, and
+    /// unarchive those that are back
+    pub fn archive_stale_sessions(
+        &mut self,
+        current_uuids: &HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT uuid, missing_since FROM sessions")?;
+        let all_sessions: Vec<(String, Option<i64>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .filter_map(Result::ok)
+            .collect();
+        drop(stmt);
+
+        let now = unix_now()?;
+        for (uuid, missing_since) in all_sessions {
+            let missing_since = match (current_uuids.contains(&uuid), missing_since) {
+                (true, Some(_)) => None,
+                (false, None) => Some(now),
+                _ => continue,
+            };
+            self.conn.execute(
+                "UPDATE sessions SET missing_since = ?1 WHERE uuid = ?2",
+                params![missing_since, uuid],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Permanently delete repos and sessions archived at or before `cutoff`
+    /// (seconds since epoch), along with the worktrees of purged repos
+    pub fn purge_archived(&mut self, cutoff: i64) -> Result<PurgeStats, Box<dyn Error>> {
+        let tx = self.conn.transaction()?;
+
+        for table in [
+            "session_text",
+            "session_usage",
+            "session_files",
+            "session_messages",
+            "session_links",
+            "session_lineage",
+        ] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE uuid IN
+                        (SELECT uuid FROM sessions WHERE missing_since <= ?1)",
+                    table
+                ),
+                params![cutoff],
+            )?;
+        }
+        tx.execute(
+            "DELETE FROM session_lineage WHERE parent_uuid IN
+                (SELECT uuid FROM sessions WHERE missing_since <= ?1)",
+            params![cutoff],
+        )?;
+        let sessions = tx.execute(
+            "DELETE FROM sessions WHERE missing_since <= ?1",
+            params![cutoff],
+        )?;
+
+        let purged_repos = "SELECT id FROM repos WHERE missing_since <= ?1";
+        tx.execute(
+            &format!(
+                "UPDATE sessions SET repo_id = NULL, worktree_id = NULL WHERE repo_id IN ({})",
+                purged_repos
+            ),
+            params![cutoff],
+        )?;
+        tx.execute(
+            &format!("DELETE FROM worktrees WHERE repo_id IN ({})", purged_repos),
+            params![cutoff],
+        )?;
+        let repos = tx.execute(
+            "DELETE FROM repos WHERE missing_since <= ?1",
+            params![cutoff],
+        )?;
+
+        tx.commit()?;
+        Ok(PurgeStats { repos, sessions })
+    }
+
+    pub fn record_scan(&mut self, stats: &ScanStats) -> Result<(), Box<dyn Error>> {
+        self.conn.execute(
+            "INSERT INTO scans (started_at, duration_ms, repo_count, session_count)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                stats.started_at,
+                stats.duration_ms,
+                stats.repo_count,
+                stats.session_count
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the repos, branches and sessions matching the SQL part of `query`:
+    /// repos by name, branches most recent first, sessions newest first.
+    /// Fuzzy text, `dirty` and the recent window are applied in memory by the
+    /// TUI, so this only runs when the data or those SQL terms change.
+    /// Archived repos and sessions are left out unless `include_archived` is set.
+    ///
+    /// The tree is loaded with a few set-based queries and grouped here rather
+    /// than queried per repo and branch.
+    pub fn get_repos_with_data(
+        &self,
+        query: &Query,
+        include_archived: bool,
+    ) -> Result<Vec<RepoData>, Box<dyn Error>> {
+        let filter = query.to_sql(2);
+        let mut params: Vec<Value> = vec![include_archived.into()];
+        params.extend(filter.params);
+
+        // Without query terms, visible branches are picked from idx_sessions_repo_branch alone
+        let visible_branches = format!(
+            "SELECT r.id, r.name, r.path, r.missing_since, s.git_branch,
+                    MAX(s.modified) AS last_modified
+             FROM sessions s
+             JOIN repos r ON r.id = s.repo_id
+             WHERE (?1 OR (s.missing_since IS NULL AND r.missing_since IS NULL))
+               AND EXISTS (SELECT 1 FROM worktrees w WHERE w.repo_id = r.id)
+               AND {}
+             GROUP BY r.id, s.git_branch",
+            filter.clause
+        );
+
+        // Build the repo -> branch skeleton, by repo name and most recent branch first
+        let mut stmt = self.conn.prepare(&format!(
+            "{} ORDER BY r.name, r.id, last_modified DESC, s.git_branch",
+            visible_branches
+        ))?;
+        let mut result: Vec<RepoData> = Vec::new();
+        let mut repo_ids = Vec::new();
+        let mut branch_index: HashMap<(i64, Option<String>), (usize, usize)> = HashMap::new();
+        let mut rows = stmt.query(params_from_iter(&params))?;
+        while let Some(row) = rows.next()? {
+            let repo_id: i64 = row.get(0)?;
+            if repo_ids.last() != Some(&repo_id) {
+                repo_ids.push(repo_id);
+                result.push(RepoData {
+                    path: PathBuf::from(row.get::<_, String>(2)?),
+                    name: row.get(1)?,
+                    worktrees: Vec::new(),
+                    branches: Vec::new(),
+                    missing_since: row.get(3)?,
+                });
+            }
+            let repo_idx = result.len() - 1;
+            let branches = &mut result[repo_idx].branches;
+            let git_branch: Option<String> = row.get(4)?;
+            branch_index.insert((repo_id, git_branch.clone()), (repo_idx, branches.len()));
+            branches.push(BranchData {
+                branch: git_branch.unwrap_or_else(|| NO_BRANCH.to_string()),
+                sessions: Vec::new(),
+            });
+        }
+        drop(rows);
+        drop(stmt);
+
+        // Fill in the sessions of those branches; sorting them here is much
+        // cheaper than having SQLite sort the wide rows
+        let mut stmt = self.conn.prepare(&format!(
+            "WITH visible AS ({})
+             SELECT s.repo_id, s.git_branch, s.uuid, s.project_path, s.summary, s.first_prompt,
+                    s.modified, s.message_count, s.provider, s.missing_since, s.file_path,
+                    s.agent_home
+             FROM visible b
+             JOIN sessions s ON s.repo_id = b.id AND s.git_branch IS b.git_branch
+             JOIN repos r ON r.id = s.repo_id
+             WHERE (?1 OR s.missing_since IS NULL) AND {}",
+            visible_branches, filter.clause
+        ))?;
+        let mut rows = stmt.query(params_from_iter(&params))?;
+        while let Some(row) = rows.next()? {
+            let key = (row.get(0)?, row.get(1)?);
+            let Some(&(repo_idx, branch_idx)) = branch_index.get(&key) else {
+                continue;
+            };
+            result[repo_idx].branches[branch_idx]
+                .sessions
+                .push(SessionData {
+                    uuid: row.get(2)?,
+                    project_path: row.get(3)?,
+                    summary: row.get(4)?,
+                    first_prompt: row.get(5)?,
+                    modified: row.get(6)?,
+                    message_count: row.get(7)?,
+                    provider: row.get(8)?,
+                    missing_since: row.get(9)?,
+                    snippet: None,
+                    file_path: row.get::<_, Option<String>>(10)?.map(PathBuf::from),
+                    home: row.get::<_, Option<String>>(11)?.map(PathBuf::from),
+                    usage: Vec::new(),
+                    link: None,
+                    lineage: Vec::new(),
+                });
+        }
+        for branch in result.iter_mut().flat_map(|r| r.branches.iter_mut()) {
+            branch
+                .sessions
+                .sort_by_key(|s| std::cmp::Reverse(s.modified));
+        }
+        self.fill_usage(&mut result)?;
+        self.group_lineage(&mut result)?;
+
+        let mut worktrees = self.get_worktrees_by_repo()?;
+        for (repo, repo_id) in result.iter_mut().zip(&repo_ids) {
+            repo.worktrees = worktrees.remove(repo_id).unwrap_or_default();
+        }
+
+        Ok(result)
+    }
+
+    /// Full-text search over session prompts, replies and summaries, best
+    /// match first. Each session appears once, with a snippet of its best
+    /// matching text. Returns nothing for a query without any terms.
+    pub fn search_sessions(
+        &self,
+        query: &str,
+        include_archived: bool,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        // bm25 and snippet only work in the FTS query itself, hence MATERIALIZED;
+        // MIN() picks the snippet of each session's best row
+        let mut stmt = self.conn.prepare(
+            "WITH hits AS MATERIALIZED (
+                SELECT t.uuid, bm25(session_text_fts) AS score,
+                       snippet(session_text_fts, 0, char(1), char(2), '…', 16) AS snippet
+                FROM session_text_fts
+                JOIN session_text t ON t.id = session_text_fts.rowid
+                WHERE session_text_fts MATCH ?1
+             ),
+             best AS (
+                SELECT uuid, MIN(score) AS score, snippet FROM hits GROUP BY uuid
+             )
+             SELECT s.uuid, s.project_path, s.summary, s.first_prompt, s.modified,
+                    s.message_count, s.provider, s.missing_since, b.snippet,
+                    s.git_branch, r.id, r.name, r.path, r.missing_since, s.file_path,
+                    s.agent_home
+             FROM best b
+             JOIN sessions s ON s.uuid = b.uuid
+             LEFT JOIN repos r ON r.id = s.repo_id
+             WHERE ?2 OR (s.missing_since IS NULL AND r.missing_since IS NULL)
+             ORDER BY b.score
+             LIMIT ?3",
+        )?;
+
+        let hits = stmt
+            .query_map(params![query, include_archived, limit as i64], |row| {
+                Ok(SearchHit {
+                    session: SessionData {
+                        uuid: row.get(0)?,
+                        project_path: row.get(1)?,
+                        summary: row.get(2)?,
+                        first_prompt: row.get(3)?,
+                        modified: row.get(4)?,
+                        message_count: row.get(5)?,
+                        provider: row.get(6)?,
+                        missing_since: row.get(7)?,
+                        snippet: row.get(8)?,
+                        file_path: row.get::<_, Option<String>>(14)?.map(PathBuf::from),
+                        home: row.get::<_, Option<String>>(15)?.map(PathBuf::from),
+                        usage: Vec::new(),
+                        link: None,
+                        lineage: Vec::new(),
+                    },
+                    branch: row
+                        .get::<_, Option<String>>(9)?
+                        .unwrap_or_else(|| NO_BRANCH.to_string()),
+                    repo_id: row.get(10)?,
+                    repo_name: row.get(11)?,
+                    repo_path: row.get::<_, Option<String>>(12)?.map(PathBuf::from),
+                    repo_missing_since: row.get(13)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(hits)
+    }
+
+    /// Text search results shaped as a repo tree: repos and branches in order
+    /// of their best match, sessions ranked within each branch
+    pub fn get_repos_for_search(
+        &self,
+        query: &str,
+        include_archived: bool,
+    ) -> Result<Vec<RepoData>, Box<dyn Error>> {
+        let mut worktrees = self.get_worktrees_by_repo()?;
+        let mut result: Vec<RepoData> = Vec::new();
+        let mut repo_index: HashMap<i64, usize> = HashMap::new();
+
+        for hit in self.search_sessions(query, include_archived, SEARCH_TREE_LIMIT)? {
+            let (Some(repo_id), Some(name), Some(path)) =
+                (hit.repo_id, hit.repo_name, hit.repo_path)
+            else {
+                continue;
+            };
+            // Same rule as the normal tree: only repos with worktrees to launch in
+            let repo_idx = match repo_index.get(&repo_id) {
+                Some(&idx) => idx,
+                None => {
+                    let Some(repo_worktrees) = worktrees.remove(&repo_id) else {
+                        continue;
+                    };
+                    repo_index.insert(repo_id, result.len());
+                    result.push(RepoData {
+                        path,
+                        name,
+                        worktrees: repo_worktrees,
+                        branches: Vec::new(),
+                        missing_since: hit.repo_missing_since,
+                    });
+                    result.len() - 1
+                }
+            };
+
+            let branches = &mut result[repo_idx].branches;
+            match branches.iter_mut().find(|b| b.branch == hit.branch) {
+                Some(branch) => branch.sessions.push(hit.session),
+                None => branches.push(BranchData {
+                    branch: hit.branch,
+                    sessions: vec![hit.session],
+                }),
+            }
+        }
+        self.fill_usage(&mut result)?;
+
+        Ok(result)
+    }
+
+    /// Attach each session's per-model token usage
+    fn fill_usage(&self, repos: &mut [RepoData]) -> Result<(), Box<dyn Error>> {
+        let mut usage: HashMap<String, Vec<ModelUsage>> = HashMap::new();
+        let mut stmt = self.conn.prepare(
+            "SELECT uuid, model, SUM(input_tokens), SUM(output_tokens),
+                    SUM(cache_creation_tokens), SUM(cache_read_tokens)
+             FROM session_usage
+             GROUP BY uuid, model
+             ORDER BY SUM(output_tokens) DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            usage.entry(row.get(0)?).or_default().push(ModelUsage {
+                model: row.get(1)?,
+                tokens: TokenUsage {
+                    input: row.get(2)?,
+                    output: row.get(3)?,
+                    cache_creation: row.get(4)?,
+                    cache_read: row.get(5)?,
+                },
+            });
+        }
+
+        for session in repos
+            .iter_mut()
+            .flat_map(|r| r.branches.iter_mut())
+            .flat_map(|b| b.sessions.iter_mut())
+        {
+            session.usage = usage.remove(&session.uuid).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// Fold each branch's sessions of one lineage into a single chain: its
+    /// newest leaf, carrying the chain's other sessions newest first. The
+    /// chain takes the place of its most recently modified session.
+    fn group_lineage(&self, repos: &mut [RepoData]) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT uuid, parent_uuid, kind FROM session_lineage")?;
+        let parents: HashMap<String, (String, Option<LinkKind>)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    (row.get(1)?, LinkKind::parse(&row.get::<_, String>(2)?)),
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        if parents.is_empty() {
+            return Ok(());
+        }
+
+        let root_of = |uuid: &str| {
+            let mut root = uuid;
+            let mut seen = HashSet::new();
+            while let Some((parent, _)) = parents.get(root) {
+                if !seen.insert(root) {
+                    break;
+                }
+                root = parent;
+            }
+            root.to_string()
+        };
+
+        let linked: HashSet<&str> = parents
+            .iter()
+            .flat_map(|(uuid, (parent, _))| [uuid.as_str(), parent.as_str()])
+            .collect();
+
+        for branch in repos.iter_mut().flat_map(|r| r.branches.iter_mut()) {
+            if !branch
+                .sessions
+                .iter()
+                .any(|s| linked.contains(s.uuid.as_str()))
+            {
+                continue;
+            }
+            let roots: Vec<String> = branch.sessions.iter().map(|s| root_of(&s.uuid)).collect();
+            let mut chains: HashMap<&str, Vec<SessionData>> = HashMap::new();
+            for (mut session, root) in std::mem::take(&mut branch.sessions).into_iter().zip(&roots)
+            {
+                session.link = parents.get(&session.uuid).and_then(|(_, kind)| *kind);
+                chains.entry(root).or_default().push(session);
+            }
+
+            // Sessions are newest first, so each chain is emitted at its newest
+            let mut sessions = Vec::with_capacity(chains.len());
+            for root in &roots {
+                let Some(mut chain) = chains.remove(root.as_str()) else {
+                    continue;
+                };
+                let is_parent = |uuid: &str| {
+                    chain
+                        .iter()
+                        .any(|s| parents.get(&s.uuid).is_some_and(|(p, _)| p == uuid))
+                };
+                let leaf = chain.iter().position(|s| !is_parent(&s.uuid)).unwrap_or(0);
+                let mut head = chain.remove(leaf);
+                head.lineage = chain;
+                sessions.push(head);
+            }
+            branch.sessions = sessions;
+        }
+        Ok(())
+    }
+
+    /// Token usage since `since` (ms since epoch) of every session, archived
+    /// ones included, one row per group, session and model. Days are those
+    /// the replies were written on.
+    pub fn get_usage(
+        &self,
+        group: UsageGroup,
+        since: Option<i64>,
+    ) -> Result<Vec<UsageRow>, Box<dyn Error>> {
+        let group = match group {
+            UsageGroup::Repo => "COALESCE(r.name, s.project_path)".to_string(),
+            UsageGroup::Branch => format!(
+                "COALESCE(r.name, s.project_path) || ' [' || COALESCE(s.git_branch, '{}') || ']'",
+                NO_BRANCH
+            ),
+            UsageGroup::Day => "date(u.timestamp / 1000, 'unixepoch', 'localtime')".to_string(),
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, u.uuid, u.model, SUM(u.input_tokens), SUM(u.output_tokens),
+                    SUM(u.cache_creation_tokens), SUM(u.cache_read_tokens)
+             FROM session_usage u
+             JOIN sessions s ON s.uuid = u.uuid
+             LEFT JOIN repos r ON r.id = s.repo_id
+             WHERE u.timestamp >= ?1
+             GROUP BY 1, u.uuid, u.model",
+            group
+        ))?;
+
+        let rows = stmt
+            .query_map(params![since.unwrap_or(0)], |row| {
+                Ok(UsageRow {
+                    group: row.get(0)?,
+                    uuid: row.get(1)?,
+                    model: row.get(2)?,
+                    tokens: TokenUsage {
+                        input: row.get(3)?,
+                        output: row.get(4)?,
+                        cache_creation: row.get(5)?,
+                        cache_read: row.get(6)?,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// All worktrees (unfiltered), keyed by repo id and ordered by path
+    fn get_worktrees_by_repo(&self) -> Result<HashMap<i64, Vec<WorktreeInfo>>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT w.repo_id, w.path, w.branch
+             FROM worktrees w
+             ORDER BY w.path",
+        )?;
+
+        let mut worktrees: HashMap<i64, Vec<WorktreeInfo>> = HashMap::new();
+        let rows = stmt.query_map([], |row| {
+            let repo_id: i64 = row.get(0)?;
+            let path_str: String = row.get(1)?;
+            let branch: Option<String> = row.get(2)?;
+            let path = PathBuf::from(&path_str);
+            let name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| path_str.clone());
+            Ok((
+                repo_id,
+                WorktreeInfo {
+                    path,
+                    name,
+                    checked_out_branch: branch,
+                },
+            ))
+        })?;
+        for (repo_id, worktree) in rows.filter_map(Result::ok) {
+            worktrees.entry(repo_id).or_default().push(worktree);
+        }
+
+        Ok(worktrees)
+    }
+}
+
+/// How many sessions the TUI shows for a text search
+const SEARCH_TREE_LIMIT: usize = 200;
+
+/// Turn free text into an FTS5 query matching all of its words, so quotes,
+/// dashes and operators typed by the user are taken literally. The last word
+/// also matches as a prefix, since it may still be being typed.
+fn fts_query(input: &str) -> Option<String> {
+    let words: Vec<String> = input
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    let last = words.last()?;
+    Some(
+        format!("{} {}*", words[..words.len() - 1].join(" "), last)
+            .trim_start()
+            .to_string(),
+    )
+}
+
+fn unix_now() -> Result<i64, Box<dyn Error>> {
+    Ok(std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::claude::{MessageUsage, SessionLink, TextKind, TranscriptText};
+    use crate::scanner::git::Worktree;
+
+    fn database() -> Database {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations::run(&mut conn).unwrap();
+        Database { conn }
+    }
+
+    fn session(uuid: &str, cwd: &str) -> Session {
+        Session {
+            uuid: uuid.to_string(),
+            project_path: cwd.to_string(),
+            git_branch: Some("main".to_string()),
+            summary: None,
+            first_prompt: None,
+            modified: 1_000,
+            message_count: Some(1),
+            provider: "claude".to_string(),
+            home: None,
+            checkpoint: None,
+            transcript: Vec::new(),
+            usage: Vec::new(),
+            files: BTreeSet::new(),
+            boundary_ids: Vec::new(),
+            links: Vec::new(),
+            transcript_resumed: false,
+        }
+    }
+
+    /// Record a repo at `path` with its main worktree and `linked` ones
+    fn add_repo(db: &mut Database, path: &str, linked: &[&str]) {
+        let repo = Repo {
+            path: PathBuf::from(path),
+            name: path.rsplit('/').next().unwrap().to_string(),
+            git_common_dir: Some(PathBuf::from(path).join(".git")),
+            worktrees: std::iter::once(path)
+                .chain(linked.iter().copied())
+                .map(|wt| Worktree {
+                    path: PathBuf::from(wt),
+                    branch: None,
+                })
+                .collect(),
+        };
+        db.upsert_repo(&repo).unwrap();
+        for worktree in &repo.worktrees {
+            db.upsert_worktree(&repo.path, worktree).unwrap();
+        }
+    }
+
+    fn add_sessions(db: &mut Database, sessions: &[Session]) -> HashSet<String> {
+        for session in sessions {
+            db.upsert_session(session).unwrap();
+        }
+        sessions.iter().map(|s| s.uuid.clone()).collect()
+    }
+
+    fn id_of(db: &Database, table: &str, path: &str) -> i64 {
+        db.conn
+            .query_row(
+                &format!("SELECT id FROM {} WHERE path = ?1", table),
+                params![path],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    /// (repo_id, worktree_id, git_common_dir) a session is linked to
+    fn link_of(db: &Database, uuid: &str) -> (Option<i64>, Option<i64>, Option<String>) {
+        db.conn
+            .query_row(
+                "SELECT repo_id, worktree_id, git_common_dir FROM sessions WHERE uuid = ?1",
+                params![uuid],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn links_sessions_to_the_worktree_their_cwd_is_in() {
+        let mut db = database();
+        let before = db.repo_layout().unwrap();
+        add_repo(&mut db, "/ws-test/api", &["/ws-test/api/.worktrees/feat"]);
+        add_repo(&mut db, "/ws-test/api-gateway", &[]);
+        let upserted = add_sessions(
+            &mut db,
+            &[
+                session("main", "/ws-test/api/src"),
+                session("feat", "/ws-test/api/.worktrees/feat"),
+                session("gateway", "/ws-test/api-gateway"),
+            ],
+        );
+        db.link_sessions(&before, &upserted, |_| None).unwrap();
+
+        let api = id_of(&db, "repos", "/ws-test/api");
+        let common_dir = Some("/ws-test/api/.git".to_string());
+        assert_eq!(
+            link_of(&db, "main"),
+            (
+                Some(api),
+                Some(id_of(&db, "worktrees", "/ws-test/api")),
+                common_dir.clone()
+            )
+        );
+        assert_eq!(
+            link_of(&db, "feat"),
+            (
+                Some(api),
+                Some(id_of(&db, "worktrees", "/ws-test/api/.worktrees/feat")),
+                common_dir
+            )
+        );
+        assert_eq!(
+            link_of(&db, "gateway").0,
+            Some(id_of(&db, "repos", "/ws-test/api-gateway"))
+        );
+    }
+
+    #[test]
+    fn resolves_cwds_outside_known_worktrees_once() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &[]);
+        let before = db.repo_layout().unwrap();
+        let upserted = add_sessions(
+            &mut db,
+            &[
+                session("gone", "/ws-test/api-old-wt"),
+                session("gone-too", "/ws-test/api-old-wt"),
+                session("loose", "/ws-test/notes"),
+            ],
+        );
+        let mut resolved = Vec::new();
+        db.link_sessions(&before, &upserted, |cwd| {
+            resolved.push(cwd.to_path_buf());
+            (cwd == Path::new("/ws-test/api-old-wt")).then(|| PathBuf::from("/ws-test/api/.git"))
+        })
+        .unwrap();
+        resolved.sort();
+        assert_eq!(
+            resolved,
+            [
+                PathBuf::from("/ws-test/api-old-wt"),
+                PathBuf::from("/ws-test/notes")
+            ]
+        );
+
+        // A deleted worktree stays with its repo through the common dir
+        let api = id_of(&db, "repos", "/ws-test/api");
+        let common_dir = Some("/ws-test/api/.git".to_string());
+        assert_eq!(link_of(&db, "gone"), (Some(api), None, common_dir.clone()));
+        assert_eq!(link_of(&db, "gone-too"), (Some(api), None, common_dir));
+        assert_eq!(
+            link_of(&db, "loose"),
+            (None, None, Some(NO_REPO.to_string()))
+        );
+
+        // Known to be outside any repo, so not resolved again
+        let before = db.repo_layout().unwrap();
+        let upserted = add_sessions(&mut db, &[session("loose", "/ws-test/notes")]);
+        db.link_sessions(&before, &upserted, |_| panic!("resolved again"))
+            .unwrap();
+    }
+
+    #[test]
+    fn relinks_sessions_when_their_worktree_appears() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &[]);
+        let before = db.repo_layout().unwrap();
+        let upserted = add_sessions(&mut db, &[session("feat", "/ws-test/api-feat")]);
+        db.link_sessions(&before, &upserted, |_| {
+            Some(PathBuf::from("/ws-test/api/.git"))
+        })
+        .unwrap();
+        assert_eq!(link_of(&db, "feat").1, None);
+
+        // A later scan finds the worktree without reading the session again
+        let before = db.repo_layout().unwrap();
+        add_repo(&mut db, "/ws-test/api", &["/ws-test/api-feat"]);
+        db.link_sessions(&before, &HashSet::new(), |_| None)
+            .unwrap();
+        assert_eq!(
+            link_of(&db, "feat").1,
+            Some(id_of(&db, "worktrees", "/ws-test/api-feat"))
+        );
+    }
+
+    fn missing_since(db: &Database, table: &str, key: &str, value: &str) -> Option<i64> {
+        db.conn
+            .query_row(
+                &format!("SELECT missing_since FROM {} WHERE {} = ?1", table, key),
+                params![value],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    fn count(db: &Database, sql: &str) -> i64 {
+        db.conn.query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    fn set(paths: &[&str]) -> HashSet<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn archives_what_a_scan_missed_and_restores_what_is_back() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &["/ws-test/api-feat"]);
+        add_repo(&mut db, "/ws-test/web", &[]);
+        add_sessions(
+            &mut db,
+            &[session("s1", "/ws-test/api"), session("s2", "/ws-test/web")],
+        );
+
+        db.archive_stale_repos(&set(&["/ws-test/api"])).unwrap();
+        db.archive_stale_sessions(&set(&["s1"])).unwrap();
+        assert_eq!(missing_since(&db, "repos", "path", "/ws-test/api"), None);
+        let archived = missing_since(&db, "repos", "path", "/ws-test/web");
+        assert!(archived.is_some());
+        assert_eq!(missing_since(&db, "sessions", "uuid", "s1"), None);
+        assert!(missing_since(&db, "sessions", "uuid", "s2").is_some());
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM sessions"), 2);
+
+        // Still missing: archived since the first scan that missed it
+        db.conn
+            .execute(
+                "UPDATE repos SET missing_since = 1 WHERE path = '/ws-test/web'",
+                [],
+            )
+            .unwrap();
+        db.archive_stale_repos(&set(&["/ws-test/api"])).unwrap();
+        assert_eq!(missing_since(&db, "repos", "path", "/ws-test/web"), Some(1));
+
+        // An archived repo keeps its worktrees
+        db.delete_stale_worktrees(&[]).unwrap();
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM worktrees"), 3);
+
+        db.archive_stale_repos(&set(&["/ws-test/api", "/ws-test/web"]))
+            .unwrap();
+        db.archive_stale_sessions(&set(&["s1", "s2"])).unwrap();
+        assert_eq!(missing_since(&db, "repos", "path", "/ws-test/web"), None);
+        assert_eq!(missing_since(&db, "sessions", "uuid", "s2"), None);
+    }
+
+    #[test]
+    fn purges_only_what_was_archived_before_the_cutoff() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &["/ws-test/api-feat"]);
+        add_repo(&mut db, "/ws-test/web", &[]);
+        let mut old = session("old", "/ws-test/web");
+        old.transcript.push(TranscriptText {
+            kind: TextKind::User,
+            text: "fix the tests".to_string(),
+        });
+        old.files.insert("/ws-test/web/main.rs".to_string());
+        let before = db.repo_layout().unwrap();
+        let upserted = add_sessions(
+            &mut db,
+            &[
+                old,
+                session("recent", "/ws-test/web"),
+                session("child", "/ws-test/api"),
+                session("kept", "/ws-test/api-feat"),
+            ],
+        );
+        db.link_sessions(&before, &upserted, |_| None).unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO session_lineage (uuid, parent_uuid, kind) VALUES
+                    ('old', 'kept', 'resume'), ('child', 'old', 'resume');
+                 UPDATE sessions SET missing_since = 100 WHERE uuid = 'old';
+                 UPDATE sessions SET missing_since = 300 WHERE uuid = 'recent';
+                 UPDATE repos SET missing_since = 100 WHERE path = '/ws-test/api';",
+            )
+            .unwrap();
+
+        let stats = db.purge_archived(200).unwrap();
+        assert_eq!((stats.repos, stats.sessions), (1, 1));
+        let uuids: Vec<String> = db
+            .conn
+            .prepare("SELECT uuid FROM sessions ORDER BY uuid")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(uuids, ["child", "kept", "recent"]);
+        for table in ["session_text", "session_files", "session_lineage"] {
+            assert_eq!(count(&db, &format!("SELECT COUNT(*) FROM {}", table)), 0);
+        }
+
+        // Sessions of a purged repo stay, unlinked
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM repos"), 1);
+        assert_eq!(count(&db, "SELECT COUNT(*) FROM worktrees"), 1);
+        assert_eq!(link_of(&db, "kept").0, None);
+    }
+
+    fn said(mut session: Session, texts: &[(TextKind, &str)]) -> Session {
+        for (kind, text) in texts {
+            session.transcript.push(TranscriptText {
+                kind: *kind,
+                text: text.to_string(),
+            });
+        }
+        session
+    }
+
+    #[test]
+    fn fts_queries_take_input_literally() {
+        assert_eq!(fts_query("   "), None);
+        assert_eq!(fts_query("migr").as_deref(), Some("\"migr\"*"));
+        assert_eq!(
+            fts_query("wal mode-switch").as_deref(),
+            Some("\"wal\" \"mode-switch\"*")
+        );
+        assert_eq!(
+            fts_query("say \"hi\" OR").as_deref(),
+            Some("\"say\" \"\"\"hi\"\"\" \"OR\"*")
+        );
+    }
+
+    #[test]
+    fn searches_transcripts_best_match_first() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &[]);
+        let before = db.repo_layout().unwrap();
+        let upserted = add_sessions(
+            &mut db,
+            &[
+                said(
+                    session("wal", "/ws-test/api"),
+                    &[
+                        (TextKind::User, "turn on WAL mode"),
+                        (
+                            TextKind::Assistant,
+                            "WAL mode is on, readers no longer block",
+                        ),
+                    ],
+                ),
+                said(
+                    session("mention", "/ws-test/api"),
+                    &[(
+                        TextKind::Summary,
+                        "Schema migrations, WAL and some long unrelated notes about the scanner and its many threads",
+                    )],
+                ),
+                said(
+                    session("other", "/ws-test/notes"),
+                    &[(TextKind::User, "fuzzy filter")],
+                ),
+            ],
+        );
+        db.link_sessions(&before, &upserted, |_| None).unwrap();
+
+        let hits = db.search_sessions("wal", false, 10).unwrap();
+        let uuids: Vec<&str> = hits.iter().map(|h| h.session.uuid.as_str()).collect();
+        assert_eq!(uuids, ["wal", "mention"]);
+        let hit = &hits[0];
+        assert_eq!(hit.repo_name.as_deref(), Some("api"));
+        assert_eq!(hit.branch, "main");
+        let snippet = hit.session.snippet.as_deref().unwrap();
+        assert!(snippet.contains(&format!("{}WAL{}", MATCH_START, MATCH_END)));
+
+        // The last word matches as a prefix, every word must match
+        assert_eq!(
+            db.search_sessions("fuzzy filt", false, 10).unwrap().len(),
+            1
+        );
+        assert!(
+            db.search_sessions("fuzzy wal", false, 10)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(db.search_sessions("\"", false, 10).unwrap().is_empty());
+
+        // Archived sessions only on request; a re-read replaces old text
+        db.archive_stale_sessions(&set(&["wal", "other"])).unwrap();
+        assert_eq!(db.search_sessions("wal", false, 10).unwrap().len(), 1);
+        assert_eq!(db.search_sessions("wal", true, 10).unwrap().len(), 2);
+        add_sessions(
+            &mut db,
+            &[said(
+                session("wal", "/ws-test/api"),
+                &[(TextKind::User, "journal mode")],
+            )],
+        );
+        let hits = db.search_sessions("wal", true, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session.uuid, "mention");
+    }
+
+    fn reply(id: &str, model: &str, timestamp: i64, output: i64) -> MessageUsage {
+        MessageUsage {
+            id: id.to_string(),
+            model: model.to_string(),
+            timestamp,
+            tokens: TokenUsage {
+                input: 1,
+                output,
+                ..TokenUsage::default()
+            },
+        }
+    }
+
+    fn usage(db: &Database, group: UsageGroup, since: Option<i64>) -> Vec<(String, String, i64)> {
+        let mut rows: Vec<_> = db
+            .get_usage(group, since)
+            .unwrap()
+            .into_iter()
+            .map(|row| (row.group, row.model, row.tokens.total()))
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    #[test]
+    fn counts_a_reply_read_again_once() {
+        let mut db = database();
+        add_repo(&mut db, "/ws-test/api", &[]);
+        let before = db.repo_layout().unwrap();
+        let mut first = session("s1", "/ws-test/api");
+        first.usage = vec![reply("msg_1", "sonnet", 1_000, 10)];
+        let upserted = add_sessions(&mut db, &[first.clone()]);
+        db.link_sessions(&before, &upserted, |_| None).unwrap();
+
+        // The next pass resumes in the middle of msg_1
+        let mut second = first.clone();
+        second.transcript_resumed = true;
+        second.usage = vec![
+            reply("msg_1", "sonnet", 1_000, 30),
+            reply("msg_2", "haiku", 2_000, 5),
+        ];
+        add_sessions(&mut db, &[second]);
+        assert_eq!(
+            usage(&db, UsageGroup::Repo, None),
+            [
+                ("api".to_string(), "haiku".to_string(), 6),
+                ("api".to_string(), "sonnet".to_string(), 31),
+            ]
+        );
+
+        // A full re-parse replaces everything
+        first.usage = vec![reply("msg_3", "sonnet", 3_000, 2)];
+        add_sessions(&mut db, &[first]);
+        assert_eq!(
+            usage(&db, UsageGroup::Branch, None),
+            [("api [main]".to_string(), "sonnet".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn groups_usage_by_the_day_of_each_reply() {
+        use chrono::TimeZone;
+
+        let mut db = database();
+        let day = 86_400_000;
+        let monday = 1_716_206_400_000; // 2024-05-20 12:00 UTC
+        let mut s1 = session("s1", "/ws-test/notes");
+        s1.usage = vec![
+            reply("msg_1", "sonnet", monday, 10),
+            reply("msg_2", "sonnet", monday + day, 20),
+            reply("msg_3", "sonnet", monday + 2 * day, 30),
+        ];
+        add_sessions(&mut db, &[s1]);
+
+        let date = |ms: i64| {
+            chrono::Local
+                .timestamp_millis_opt(ms)
+                .unwrap()
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        assert_eq!(
+            usage(&db, UsageGroup::Day, Some(monday + day)),
+            [
+                (date(monday + day), "sonnet".to_string(), 21),
+                (date(monday + 2 * day), "sonnet".to_string(), 31),
+            ]
+        );
+        assert_eq!(usage(&db, UsageGroup::Repo, None).len(), 1);
+    }
+
+    #[test]
+    fn keeps_files_of_earlier_passes() {
+        let mut db = database();
+        let mut first = session("s1", "/ws-test/api");
+        first.files = ["/ws-test/api/b.rs".to_string()].into();
+        add_sessions(&mut db, &[first.clone()]);
+
+        let mut second = first.clone();
+        second.transcript_resumed = true;
+        second.files = ["/ws-test/api/a.rs", "/ws-test/api/b.rs"]
+            .map(String::from)
+            .into();
+        add_sessions(&mut db, &[second]);
+        assert_eq!(
+            db.get_session_files("s1").unwrap(),
+            ["/ws-test/api/a.rs", "/ws-test/api/b.rs"]
+        );
+
+        first.files = ["/ws-test/api/c.rs".to_string()].into();
+        add_sessions(&mut db, &[first]);
+        assert_eq!(db.get_session_files("s1").unwrap(), ["/ws-test/api/c.rs"]);
+    }
+
+    /// A session modified at `modified` holding `messages` and with `links`
+    fn linked(uuid: &str, modified: i64, messages: &[&str], links: &[(LinkKind, &str)]) -> Session {
+        let mut session = session(uuid, "/ws-test/api");
+        session.modified = modified;
+        session.boundary_ids = messages.iter().map(|m| m.to_string()).collect();
+        session.links = links
+            .iter()
+            .map(|(kind, target)| SessionLink {
+                kind: *kind,
+                target: target.to_string(),
+            })
+            .collect();
+        session
+    }
+
+    fn lineage(db: &Database) -> Vec<(String, String, String)> {
+        db.conn
+            .prepare("SELECT uuid, parent_uuid, kind FROM session_lineage ORDER BY uuid")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    fn edge(uuid: &str, parent: &str, kind: &str) -> (String, String, String) {
+        (uuid.to_string(), parent.to_string(), kind.to_string())
+    }
+
+    #[test]
+    fn links_sessions_to_the_newest_older_holder_of_a_message() {
+        let mut db = database();
+        let upserted = add_sessions(
+            &mut db,
+            &[
+                // m1 was copied into b when b resumed a
+                linked("a", 100, &["m1"], &[(LinkKind::Resume, "m1")]),
+                linked("b", 200, &["m1", "m2"], &[(LinkKind::Resume, "m1")]),
+                linked("c", 300, &["m3"], &[(LinkKind::Compact, "m1")]),
+                linked("d", 150, &["m4"], &[(LinkKind::Resume, "m1")]),
+                linked("agent", 50, &[], &[(LinkKind::Sidechain, "b")]),
+                linked("lost", 400, &[], &[(LinkKind::Resume, "gone")]),
+            ],
+        );
+        db.link_lineage(&upserted).unwrap();
+        assert_eq!(
+            lineage(&db),
+            [
+                edge("agent", "b", "sidechain"),
+                edge("b", "a", "resume"),
+                edge("c", "b", "compact"),
+                edge("d", "a", "resume"),
+            ]
+        );
+
+        // A new session holding m1 takes over only the links it is a better parent for
+        let upserted = add_sessions(&mut db, &[linked("e", 250, &["m1"], &[])]);
+        db.link_lineage(&upserted).unwrap();
+        assert_eq!(
+            lineage(&db),
+            [
+                edge("agent", "b", "sidechain"),
+                edge("b", "a", "resume"),
+                edge("c", "e", "compact"),
+                edge("d", "a", "resume"),
+            ]
+        );
+
+        // A re-read parent drops links to messages it no longer holds
+        let upserted = add_sessions(&mut db, &[linked("e", 250, &["m5"], &[])]);
+        db.link_lineage(&upserted).unwrap();
+        assert_eq!(lineage(&db)[2], edge("c", "b", "compact"));
+    }
+}
@@ -2,88 +2,357 @@ mod actions;
 mod config;
 mod db;
 mod migrate;
+mod query;
 mod scanner;
+mod stats;
 mod tui;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use scanner::{ScanEvent, ScanResult};
+use std::collections::HashSet;
 use std::error::Error;
+use std::io::{self, IsTerminal};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Parser)]
-#[command(name = "ws", about = "Git worktree & Claude session manager")]
+#[command(
+    name = "ws",
+    about = "Git worktree & Claude session manager",
+    args_conflicts_with_subcommands = true
+)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Run background scan to update database
     #[arg(long)]
     scan: bool,
 
-    /// Filter strings (all args become the initial filter)
-    #[arg(trailing_var_arg = true)]
+    /// With --scan: skip instead of waiting if another scan is already running
+    #[arg(long)]
+    no_wait: bool,
+
+    /// Number of worker threads used for scanning (defaults to CPU count)
+    #[arg(long, short)]
+    jobs: Option<usize>,
+
+    /// Initial filter, e.g. `repo:api age:<3d -wip` (all args are joined)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     filter: Vec<String>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Database maintenance
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Search session prompts, replies and summaries
+    Search {
+        /// Words to search for; sessions must contain all of them
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Maximum number of sessions to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Token usage and estimated cost of sessions
+    Stats {
+        /// Group sessions by repo, branch or day
+        #[arg(long, value_enum, default_value = "repo")]
+        by: db::UsageGroup,
+
+        /// Only count tokens used within this window (e.g. 7d, 4w)
+        #[arg(long, value_parser = config::parse_duration)]
+        since: Option<Duration>,
+    },
+    /// Permanently delete archived repos and sessions
+    Gc {
+        /// Only purge entries missing for longer than this (e.g. 30d, 2w)
+        #[arg(long, value_parser = config::parse_duration, default_value = "30d")]
+        older_than: Duration,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Apply pending schema migrations
+    Migrate {
+        /// Only list the migrations that would be applied
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
+    let jobs = cli.jobs.unwrap_or_else(scanner::pool::default_jobs);
 
-    if cli.scan {
-        run_scan()?;
+    if let Some(command) = cli.command {
+        match command {
+            Command::Db {
+                command: DbCommand::Migrate { dry_run },
+            } => run_db_migrate(dry_run)?,
+            Command::Search { query, limit } => run_search(&query.join(" "), limit)?,
+            Command::Stats { by, since } => run_stats(by, since)?,
+            Command::Gc { older_than } => run_gc(older_than)?,
+        }
+    } else if cli.scan {
+        run_scan(jobs, cli.no_wait)?;
     } else {
         let filter = cli.filter.join(" ");
-        run_tui(filter)?;
+        run_tui(filter, jobs)?;
     }
 
     Ok(())
 }
 
-fn run_scan() -> Result<(), Box<dyn Error>> {
-    let config = config::Config::load()?;
-    run_scan_with_config(&config)
-}
+fn run_db_migrate(dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let pending = db::Database::pending_migrations()?;
+    if pending.is_empty() {
+        println!("Database is up to date");
+        return Ok(());
+    }
 
-fn run_scan_with_config(config: &config::Config) -> Result<(), Box<dyn Error>> {
-    let mut db = db::Database::open()?;
+    if !dry_run {
+        // Opening the database applies pending migrations
+        db::Database::open()?;
+    }
 
-    // Scan git repos and worktrees
-    let repos = scanner::git::scan_repos(&config.scan_dirs)?;
-    for repo in &repos {
-        db.upsert_repo(repo)?;
-        for worktree in &repo.worktrees {
-            db.upsert_worktree(&repo.path, worktree)?;
-        }
+    let verb = if dry_run { "Pending" } else { "Applied" };
+    for migration in pending {
+        println!(
+            "{} {:>3}: {}",
+            verb, migration.version, migration.description
+        );
     }
+    Ok(())
+}
 
-    // Scan Claude sessions
-    let claude_sessions = scanner::claude::scan_sessions()?;
-    for session in &claude_sessions {
-        db.upsert_session(session)?;
+fn run_search(query: &str, limit: usize) -> Result<(), Box<dyn Error>> {
+    let db = db::Database::open()?;
+    let hits = db.search_sessions(query, false, limit)?;
+    if hits.is_empty() {
+        println!("No matching sessions");
+        return Ok(());
     }
 
-    // Scan Codex sessions
-    let codex_sessions = scanner::codex::scan_sessions()?;
-    for session in &codex_sessions {
-        db.upsert_session(session)?;
+    // Highlight matches when printing to a terminal
+    let (bold, reset) = if io::stdout().is_terminal() {
+        ("\x1b[1m", "\x1b[0m")
+    } else {
+        ("", "")
+    };
+
+    for hit in hits {
+        let session = &hit.session;
+        let location = hit.repo_name.as_deref().unwrap_or(&session.project_path);
+        println!(
+            "{} [{}]  {} {}",
+            location, hit.branch, session.provider, session.uuid
+        );
+        if let Some(title) = session.summary.as_ref().or(session.first_prompt.as_ref()) {
+            println!("    {}", title.lines().next().unwrap_or_default());
+        }
+        if let Some(snippet) = &session.snippet {
+            let snippet = snippet
+                .replace(db::MATCH_START, bold)
+                .replace(db::MATCH_END, reset)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            println!("    {}", snippet);
+        }
+        println!();
     }
+    Ok(())
+}
 
-    // Cleanup stale entries - combine both session lists
-    let mut all_sessions = claude_sessions;
-    all_sessions.extend(codex_sessions);
-    db.delete_stale_repos(&repos)?;
-    db.delete_stale_sessions(&all_sessions)?;
+fn run_stats(by: db::UsageGroup, since: Option<Duration>) -> Result<(), Box<dyn Error>> {
+    let config = config::Config::load()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let since = since.map(|window| now.saturating_sub(window).as_millis() as i64);
 
+    let db = db::Database::open()?;
+    let rows = db.get_usage(by, since)?;
+    stats::print_report(by, rows, &config);
     Ok(())
 }
 
-fn run_tui(filter: String) -> Result<(), Box<dyn Error>> {
+fn run_gc(older_than: Duration) -> Result<(), Box<dyn Error>> {
+    // Don't purge rows a running scan is about to unarchive
+    let _lock = db::Database::lock_scan(true)?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let cutoff = now.saturating_sub(older_than).as_secs() as i64;
+
+    let mut db = db::Database::open()?;
+    let purged = db.purge_archived(cutoff)?;
+    println!(
+        "Purged {} archived repos and {} archived sessions",
+        purged.repos, purged.sessions
+    );
+    Ok(())
+}
+
+fn run_scan(jobs: usize, no_wait: bool) -> Result<(), Box<dyn Error>> {
+    let config = config::Config::load()?;
+    let Some(stats) = run_scan_with_config(&config, jobs, !no_wait, None)? else {
+        println!("Another scan is already running, skipping");
+        return Ok(());
+    };
+    println!(
+        "Scanned {} repos and {} sessions in {:.1}s",
+        stats.repo_count,
+        stats.session_count,
+        stats.duration_ms as f64 / 1000.0
+    );
+    for warning in &stats.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(())
+}
+
+/// Scan repos and sessions into the database, optionally reporting progress.
+///
+/// Returns `None` without scanning if another scan holds the lock and `wait`
+/// is false. Repos and session files are read first, outside any transaction,
+/// so the database is only locked while the results are written.
+fn run_scan_with_config(
+    config: &config::Config,
+    jobs: usize,
+    wait: bool,
+    progress: Option<&mpsc::Sender<ScanEvent>>,
+) -> Result<Option<db::ScanStats>, Box<dyn Error>> {
+    let Some(_lock) = db::Database::lock_scan(wait)? else {
+        return Ok(None);
+    };
+
+    let started = Instant::now();
+    let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+    let mut db = db::Database::open()?;
+    let providers = scanner::Registry::new(config);
+
+    // Discover repos and session files, then parse them on a worker pool
+    let layout = db.repo_layout()?;
+    let plan = scanner::ScanPlan::discover(config, &providers, db.get_session_checkpoints()?)?;
+    let repos_total = plan.repo_count();
+    let sessions_total = plan.session_count();
+
+    let mut repos = Vec::new();
+    let mut repo_paths = HashSet::new();
+    let mut sessions = Vec::new();
+    let mut session_uuids = HashSet::new();
+    let mut warnings = Vec::new();
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        s.spawn(move || plan.run(jobs, tx));
+
+        for result in rx {
+            match result {
+                ScanResult::Repo(repo) => {
+                    repo_paths.insert(repo.path.to_string_lossy().to_string());
+                    repos.push(repo);
+                }
+                ScanResult::RepoFailed(path, warning) => {
+                    repo_paths.insert(path.to_string_lossy().to_string());
+                    warnings.push(warning);
+                }
+                ScanResult::Sessions(parsed) => {
+                    session_uuids.extend(parsed.iter().map(|s| s.uuid.clone()));
+                    sessions.extend(parsed);
+                }
+                ScanResult::Unchanged(uuid) => {
+                    session_uuids.insert(uuid);
+                }
+                ScanResult::SessionsFailed(uuids, warning) => {
+                    session_uuids.extend(uuids);
+                    warnings.push(warning);
+                }
+                ScanResult::Warning(warning) => warnings.push(warning),
+            }
+
+            if let Some(progress) = progress {
+                let _ = progress.send(ScanEvent::Progress {
+                    repos_done: repo_paths.len(),
+                    repos_total,
+                    sessions_done: session_uuids.len(),
+                    sessions_total,
+                });
+            }
+        }
+    });
+    warnings.extend(providers.iter().flat_map(|p| p.warnings()));
+
+    db.in_transaction(|db| {
+        for repo in &repos {
+            db.upsert_repo(repo)?;
+            for worktree in &repo.worktrees {
+                db.upsert_worktree(&repo.path, worktree)?;
+            }
+        }
+        let mut upserted = HashSet::new();
+        for session in &sessions {
+            db.upsert_session(session)?;
+            upserted.insert(session.uuid.clone());
+        }
+
+        // Archive whatever wasn't found; `ws gc` purges it for good
+        db.archive_stale_repos(&repo_paths)?;
+        db.delete_stale_worktrees(&repos)?;
+        db.archive_stale_sessions(&session_uuids)?;
+
+        // Attach sessions to the worktrees they ran in
+        db.link_sessions(&layout, &upserted, scanner::git::git_common_dir)?;
+        // Chain resumed, compacted and subagent sessions to the ones they continue
+        db.link_lineage(&upserted)?;
+
+        let stats = db::ScanStats {
+            started_at,
+            duration_ms: started.elapsed().as_millis() as i64,
+            repo_count: repos.len() as i64,
+            session_count: session_uuids.len() as i64,
+            warnings,
+        };
+        db.record_scan(&stats)?;
+
+        Ok(Some(stats))
+    })
+}
+
+fn run_tui(filter: String, jobs: usize) -> Result<(), Box<dyn Error>> {
     // Cleanup old launch configs from previous runs
     actions::cleanup_old_configs()?;
 
     let config = config::Config::load()?;
 
-    if config.scan_on_open {
-        run_scan_with_config(&config)?;
-    }
+    // Open straight away on what's already in the DB and scan in the background
+    let scan_events = if config.scan_on_open {
+        let (tx, rx) = mpsc::channel();
+        let scan_config = config.clone();
+        thread::spawn(move || {
+            // Don't queue behind another scan; its results will show up on next open
+            let event = match run_scan_with_config(&scan_config, jobs, false, Some(&tx)) {
+                Ok(Some(stats)) => ScanEvent::Finished(Ok(stats)),
+                Ok(None) => ScanEvent::Skipped,
+                Err(e) => ScanEvent::Finished(Err(e.to_string())),
+            };
+            let _ = tx.send(event);
+        });
+        Some(rx)
+    } else {
+        None
+    };
 
     let db = db::Database::open()?;
 
-    tui::run(db, config, filter)?;
+    tui::run(db, config, filter, scan_events)?;
 
     Ok(())
 }
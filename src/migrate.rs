@@ -8,24 +8,24 @@ pub fn path_to_project_dir(path: &Path) -> String {
     path.to_string_lossy().replace('/', "-")
 }
 
-/// Get the Claude projects directory (~/.claude/projects)
-fn claude_projects_dir() -> Result<PathBuf, Box<dyn Error>> {
-    let home = dirs::home_dir().ok_or("Could not find home directory")?;
-    Ok(home.join(".claude").join("projects"))
+/// Get the projects directory of a Claude home (e.g. ~/.claude/projects)
+fn claude_projects_dir(home: &Path) -> PathBuf {
+    home.join("projects")
 }
 
 /// Migrate a Claude session from one worktree to another.
 ///
 /// Copies the session JSONL file from the source project directory to the
 /// target project directory. Claude Code resolves sessions directly from
-/// JSONL files under ~/.claude/projects/<encoded-cwd>/, so having the file
-/// present in the target directory is sufficient.
+/// JSONL files under <home>/projects/<encoded-cwd>/, so having the file
+/// present in the target directory of the same home is sufficient.
 pub fn migrate_session(
     session_uuid: &str,
+    home: &Path,
     source_project_path: &Path,
     target_project_path: &Path,
 ) -> Result<(), Box<dyn Error>> {
-    let projects_dir = claude_projects_dir()?;
+    let projects_dir = claude_projects_dir(home);
 
     let source_dir_name = path_to_project_dir(source_project_path);
     let target_dir_name = path_to_project_dir(target_project_path);
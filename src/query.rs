@@ -0,0 +1,631 @@
+use crate::config::parse_duration;
+use crate::scanner::Registry;
+use rusqlite::types::Value;
+use std::time::Duration;
+
+/// A parsed filter, as typed into the TUI or passed as `ws <filter>` args.
+///
+/// Terms are separated by whitespace, or start wherever a filter key is
+/// typed (`repo:apibranch:main`), and must all hold. A term is negated with a
+/// leading `-` or a preceding `NOT`; `AND` may be written out but is implied.
+/// Values with spaces can be double-quoted, and `|` separates alternatives
+/// (`provider:claude|codex`). Anything else, including quoted text and words
+/// with a colon that isn't a filter key, is text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub kind: TermKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TermKind {
+    /// A bare word: fuzzy-matched against repo and branch names and session
+    /// titles, or excluded as a substring of any of them when negated
+    Text(String),
+    Repo(Vec<String>),
+    Branch(Vec<String>),
+    Provider(Vec<String>),
+    /// Worktree (folder) name the session ran in
+    Worktree(Vec<String>),
+    /// Path of a file the session wrote
+    File(Vec<String>),
+    /// Time since the session was last modified
+    Age(Comparison, Duration),
+    Messages(Comparison, i64),
+    /// Branch is checked out in a worktree with uncommitted changes
+    Dirty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+/// A condition over `sessions s JOIN repos r`, with its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlFilter {
+    pub clause: String,
+    pub params: Vec<Value>,
+}
+
+impl Query {
+    /// `providers` are the names `provider:` accepts
+    pub fn parse(input: &str, providers: &Registry) -> Result<Self, String> {
+        let mut terms = Vec::new();
+        let mut negate_next = false;
+
+        for token in tokenize(input)? {
+            match token.unquoted() {
+                "AND" if token.quoted_from.is_none() => continue,
+                "NOT" if token.quoted_from.is_none() => {
+                    negate_next = !negate_next;
+                    continue;
+                }
+                _ => {}
+            }
+            let unquoted = token.unquoted();
+            let (negated, body, unquoted) = match unquoted.strip_prefix('-') {
+                Some(rest) if token.text.len() > 1 => (!negate_next, &token.text[1..], rest),
+                _ => (negate_next, token.text.as_str(), unquoted),
+            };
+            negate_next = false;
+            terms.push(Term {
+                negated,
+                kind: parse_term(body, unquoted.len(), providers)?,
+            });
+        }
+        if negate_next {
+            return Err("NOT must be followed by a term".to_string());
+        }
+
+        Ok(Query { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The bare words, as one fuzzy pattern
+    pub fn text(&self) -> String {
+        self.terms
+            .iter()
+            .filter_map(|term| match &term.kind {
+                TermKind::Text(text) if !term.negated => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// `true` for each `dirty` term, `false` for each `-dirty`
+    pub fn dirty(&self) -> impl Iterator<Item = bool> + '_ {
+        self.terms
+            .iter()
+            .filter(|term| term.kind == TermKind::Dirty)
+            .map(|term| !term.negated)
+    }
+
+    /// Compile every term the database can evaluate into one condition.
+    /// Parameters are numbered from `first_param`, so the caller's own come first.
+    /// Fuzzy text and `dirty` are left to the caller.
+    pub fn to_sql(&self, first_param: usize) -> SqlFilter {
+        let mut sql = SqlBuilder {
+            first_param,
+            params: Vec::new(),
+        };
+        let mut conditions = Vec::new();
+
+        for term in &self.terms {
+            let condition = match &term.kind {
+                TermKind::Text(_) if !term.negated => continue,
+                TermKind::Dirty => continue,
+                TermKind::Text(text) => {
+                    let value = vec![text.to_lowercase()];
+                    format!(
+                        "({} OR {} OR {} OR {})",
+                        sql.contains("r.name", &value),
+                        sql.contains("COALESCE(s.git_branch, '')", &value),
+                        sql.contains("COALESCE(s.summary, '')", &value),
+                        sql.contains("COALESCE(s.first_prompt, '')", &value)
+                    )
+                }
+                TermKind::Repo(values) => sql.contains("r.name", values),
+                TermKind::Branch(values) => sql.contains("COALESCE(s.git_branch, '')", values),
+                TermKind::Provider(values) => {
+                    let providers: Vec<String> =
+                        values.iter().map(|v| sql.param(v.clone().into())).collect();
+                    format!("s.provider IN ({})", providers.join(", "))
+                }
+                TermKind::Worktree(values) => {
+                    // rtrim() strips the last path component off, leaving the rest as its prefix
+                    let name =
+                        "substr(w.path, length(rtrim(w.path, replace(w.path, '/', ''))) + 1)";
+                    format!(
+                        "EXISTS (SELECT 1 FROM worktrees w WHERE w.id = s.worktree_id AND {})",
+                        sql.contains(name, values)
+                    )
+                }
+                TermKind::File(values) => format!(
+                    "EXISTS (SELECT 1 FROM session_files f WHERE f.uuid = s.uuid AND {})",
+                    sql.contains("f.path", values)
+                ),
+                TermKind::Age(cmp, age) => {
+                    // Younger than the age means modified after now minus the age
+                    let age_ms = sql.param((age.as_millis() as i64).into());
+                    format!(
+                        "s.modified {} unixepoch() * 1000 - {}",
+                        cmp.flipped().sql(),
+                        age_ms
+                    )
+                }
+                TermKind::Messages(cmp, count) => format!(
+                    "COALESCE(s.message_count {} {}, 0)",
+                    cmp.sql(),
+                    sql.param((*count).into())
+                ),
+            };
+            conditions.push(if term.negated {
+                format!("NOT {}", condition)
+            } else {
+                condition
+            });
+        }
+
+        SqlFilter {
+            clause: if conditions.is_empty() {
+                "1".to_string()
+            } else {
+                conditions.join(" AND ")
+            },
+            params: sql.params,
+        }
+    }
+}
+
+/// Collects parameters for `Query::to_sql`, numbering them as they are added
+struct SqlBuilder {
+    first_param: usize,
+    params: Vec<Value>,
+}
+
+impl SqlBuilder {
+    fn param(&mut self, value: Value) -> String {
+        self.params.push(value);
+        format!("?{}", self.first_param + self.params.len() - 1)
+    }
+
+    /// Any of `values` found in the lowercased `column`
+    fn contains(&mut self, column: &str, values: &[String]) -> String {
+        let matches: Vec<String> = values
+            .iter()
+            .map(|v| {
+                format!(
+                    "instr(LOWER({}), {}) > 0",
+                    column,
+                    self.param(v.clone().into())
+                )
+            })
+            .collect();
+        format!("({})", matches.join(" OR "))
+    }
+}
+
+impl Comparison {
+    /// Split a leading `<`, `<=`, `>`, `>=` or `=` off `value`
+    fn split(value: &str) -> (Option<Self>, &str) {
+        for (prefix, cmp) in [
+            ("<=", Comparison::LessOrEqual),
+            (">=", Comparison::GreaterOrEqual),
+            ("<", Comparison::Less),
+            (">", Comparison::Greater),
+            ("=", Comparison::Equal),
+        ] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (Some(cmp), rest);
+            }
+        }
+        (None, value)
+    }
+
+    fn flipped(self) -> Self {
+        match self {
+            Comparison::Less => Comparison::Greater,
+            Comparison::LessOrEqual => Comparison::GreaterOrEqual,
+            Comparison::Greater => Comparison::Less,
+            Comparison::GreaterOrEqual => Comparison::LessOrEqual,
+            Comparison::Equal => Comparison::Equal,
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "=",
+        }
+    }
+}
+
+/// Keys of the `key:value` terms
+const KEYS: &[&str] = &["repo", "branch", "provider", "age", "msgs", "wt", "file"];
+
+/// A term as typed, with its quotes removed
+#[derive(Debug, PartialEq)]
+struct Token {
+    text: String,
+    /// Offset in `text` where the first quoted part starts
+    quoted_from: Option<usize>,
+}
+
+impl Token {
+    /// The part before any quotes, which alone can hold a filter key
+    fn unquoted(&self) -> &str {
+        &self.text[..self.quoted_from.unwrap_or(self.text.len())]
+    }
+}
+
+/// Split on whitespace outside double quotes, dropping the quotes. A filter
+/// key typed right after other text also starts a new token, as Space
+/// toggles the selected session instead of typing one.
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut current = Token {
+        text: String::new(),
+        quoted_from: None,
+    };
+    let mut quoted = false;
+    let mut in_token = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+                current.quoted_from.get_or_insert(current.text.len());
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::replace(
+                        &mut current,
+                        Token {
+                            text: String::new(),
+                            quoted_from: None,
+                        },
+                    ));
+                    in_token = false;
+                }
+            }
+            c => {
+                if c == ':'
+                    && current.quoted_from.is_none()
+                    && let Some(key) = KEYS.iter().find(|key| current.text.ends_with(*key))
+                {
+                    let mut start = current.text.len() - key.len();
+                    if current.text[..start].ends_with('-') {
+                        start -= 1;
+                    }
+                    if start > 0 {
+                        let text = current.text.split_off(start);
+                        tokens.push(std::mem::replace(
+                            &mut current,
+                            Token {
+                                text,
+                                quoted_from: None,
+                            },
+                        ));
+                    }
+                }
+                current.text.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// Parse a term whose first `unquoted` bytes were typed outside quotes
+fn parse_term(token: &str, unquoted: usize, providers: &Registry) -> Result<TermKind, String> {
+    if token == "dirty" && unquoted == token.len() {
+        return Ok(TermKind::Dirty);
+    }
+    let Some((key, _)) = token[..unquoted].split_once(':') else {
+        return Ok(TermKind::Text(token.to_string()));
+    };
+    let value = &token[key.len() + 1..];
+
+    let alternatives = || -> Result<Vec<String>, String> {
+        if value.is_empty() {
+            return Err(format!("{}: needs a value", key));
+        }
+        let values: Vec<String> = value.split('|').map(str::to_lowercase).collect();
+        if values.iter().any(String::is_empty) {
+            return Err(format!("{}: has an empty alternative", key));
+        }
+        Ok(values)
+    };
+
+    match key {
+        "repo" => Ok(TermKind::Repo(alternatives()?)),
+        "branch" => Ok(TermKind::Branch(alternatives()?)),
+        "wt" => Ok(TermKind::Worktree(alternatives()?)),
+        "file" => Ok(TermKind::File(alternatives()?)),
+        "provider" => {
+            let values = alternatives()?;
+            if let Some(unknown) = values.iter().find(|v| providers.get(v).is_none()) {
+                return Err(format!(
+                    "provider: unknown provider '{}' (expected {})",
+                    unknown,
+                    providers.describe()
+                ));
+            }
+            Ok(TermKind::Provider(values))
+        }
+        "age" => {
+            // A bare duration means "newer than"
+            let (cmp, rest) = Comparison::split(value);
+            let age = parse_duration(rest).map_err(|e| format!("age: {}", e))?;
+            Ok(TermKind::Age(cmp.unwrap_or(Comparison::Less), age))
+        }
+        "msgs" => {
+            let (cmp, rest) = Comparison::split(value);
+            let count = rest
+                .parse()
+                .map_err(|_| format!("msgs: invalid count '{}': expected e.g. >20", rest))?;
+            Ok(TermKind::Messages(cmp.unwrap_or(Comparison::Equal), count))
+        }
+        // Not a filter, such as `TODO:`
+        _ => Ok(TermKind::Text(token.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn parse(input: &str) -> Result<Query, String> {
+        Query::parse(input, &Registry::new(&Config::default()))
+    }
+
+    fn kinds(input: &str) -> Vec<(bool, TermKind)> {
+        parse(input)
+            .unwrap()
+            .terms
+            .into_iter()
+            .map(|t| (t.negated, t.kind))
+            .collect()
+    }
+
+    fn texts(input: &str) -> Vec<String> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.text)
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_splits_on_whitespace_outside_quotes() {
+        assert_eq!(
+            texts(r#"  repo:api  "fix the bug"  -"wt:my tree" "#),
+            vec!["repo:api", "fix the bug", "-wt:my tree"]
+        );
+        assert_eq!(texts(r#""""#), vec![""]);
+        assert!(tokenize(r#"fix "the bug"#).is_err());
+    }
+
+    #[test]
+    fn tokenize_starts_a_token_at_each_key() {
+        assert_eq!(
+            texts("repo:apibranch:main-wt:tmpdirtyfile:a.rs"),
+            vec!["repo:api", "branch:main", "-wt:tmpdirty", "file:a.rs"]
+        );
+        assert_eq!(texts("loginrepo:api"), vec!["login", "repo:api"]);
+        assert_eq!(texts(r#""profile:x""#), vec!["profile:x"]);
+    }
+
+    #[test]
+    fn tokens_remember_where_quotes_start() {
+        let tokens = tokenize(r#"branch:"my branch" "a b""#).unwrap();
+        assert_eq!(tokens[0].quoted_from, Some(7));
+        assert_eq!(tokens[0].unquoted(), "branch:");
+        assert_eq!(tokens[1].quoted_from, Some(0));
+    }
+
+    #[test]
+    fn parses_keys_and_alternatives() {
+        assert_eq!(
+            kinds("repo:API|web branch:feat wt:tree file:src/main.rs provider:claude|codex"),
+            vec![
+                (false, TermKind::Repo(vec!["api".into(), "web".into()])),
+                (false, TermKind::Branch(vec!["feat".into()])),
+                (false, TermKind::Worktree(vec!["tree".into()])),
+                (false, TermKind::File(vec!["src/main.rs".into()])),
+                (
+                    false,
+                    TermKind::Provider(vec!["claude".into(), "codex".into()])
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        assert_eq!(
+            kinds("age:2h age:>=1d msgs:>20 msgs:5"),
+            vec![
+                (
+                    false,
+                    TermKind::Age(Comparison::Less, Duration::from_secs(2 * 3600))
+                ),
+                (
+                    false,
+                    TermKind::Age(Comparison::GreaterOrEqual, Duration::from_secs(86400))
+                ),
+                (false, TermKind::Messages(Comparison::Greater, 20)),
+                (false, TermKind::Messages(Comparison::Equal, 5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_negation() {
+        assert_eq!(
+            kinds("-dirty NOT repo:api NOT -wip AND fix -"),
+            vec![
+                (true, TermKind::Dirty),
+                (true, TermKind::Repo(vec!["api".into()])),
+                (false, TermKind::Text("wip".into())),
+                (false, TermKind::Text("fix".into())),
+                (false, TermKind::Text("-".into())),
+            ]
+        );
+        assert!(parse("fix NOT").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_terms() {
+        for input in [
+            "repo:",
+            "repo:api|",
+            "provider:nope",
+            "age:soon",
+            "msgs:many",
+        ] {
+            assert!(parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn quoted_and_unknown_keys_are_text() {
+        assert_eq!(
+            kinds(r#"TODO: "error: foo" -"repo:api" branch:"my branch""#),
+            vec![
+                (false, TermKind::Text("TODO:".into())),
+                (false, TermKind::Text("error: foo".into())),
+                (true, TermKind::Text("repo:api".into())),
+                (false, TermKind::Branch(vec!["my branch".into()])),
+            ]
+        );
+        assert_eq!(
+            kinds(r#""dirty" "NOT" x"#),
+            vec![
+                (false, TermKind::Text("dirty".into())),
+                (false, TermKind::Text("NOT".into())),
+                (false, TermKind::Text("x".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_joins_positive_words() {
+        let query = parse("fix -wip repo:api bug").unwrap();
+        assert_eq!(query.text(), "fix bug");
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn dirty_terms() {
+        let query = parse("dirty -dirty").unwrap();
+        assert_eq!(query.dirty().collect::<Vec<_>>(), vec![true, false]);
+    }
+
+    #[test]
+    fn sql_without_database_terms_is_true() {
+        let filter = parse("fix dirty").unwrap().to_sql(1);
+        assert_eq!(filter.clause, "1");
+        assert!(filter.params.is_empty());
+    }
+
+    #[test]
+    fn sql_numbers_params_from_first_param() {
+        let filter = parse("repo:api|web provider:codex").unwrap().to_sql(3);
+        assert_eq!(
+            filter.clause,
+            "(instr(LOWER(r.name), ?3) > 0 OR instr(LOWER(r.name), ?4) > 0) AND s.provider IN (?5)"
+        );
+        assert_eq!(
+            filter.params,
+            vec![
+                Value::Text("api".into()),
+                Value::Text("web".into()),
+                Value::Text("codex".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn sql_negates_terms() {
+        let filter = parse("-WIP msgs:<=3").unwrap().to_sql(1);
+        assert_eq!(
+            filter.clause,
+            "NOT ((instr(LOWER(r.name), ?1) > 0) OR (instr(LOWER(COALESCE(s.git_branch, '')), ?2) > 0) \
+             OR (instr(LOWER(COALESCE(s.summary, '')), ?3) > 0) \
+             OR (instr(LOWER(COALESCE(s.first_prompt, '')), ?4) > 0)) \
+             AND COALESCE(s.message_count <= ?5, 0)"
+        );
+        assert_eq!(filter.params[..4], vec![Value::Text("wip".into()); 4][..]);
+        assert_eq!(filter.params[4], Value::Integer(3));
+    }
+
+    #[test]
+    fn sql_age_compares_modified_against_now() {
+        let filter = parse("age:>1h").unwrap().to_sql(1);
+        assert_eq!(filter.clause, "s.modified < unixepoch() * 1000 - ?1");
+        assert_eq!(filter.params, vec![Value::Integer(3_600_000)]);
+    }
+
+    #[test]
+    fn sql_runs_against_the_schema() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE repos (id INTEGER PRIMARY KEY, name TEXT);
+             CREATE TABLE worktrees (id INTEGER PRIMARY KEY, path TEXT);
+             CREATE TABLE session_files (uuid TEXT, path TEXT);
+             CREATE TABLE sessions (uuid TEXT, repo_id INTEGER, worktree_id INTEGER,
+                git_branch TEXT, summary TEXT, first_prompt TEXT, provider TEXT,
+                modified INTEGER, message_count INTEGER);
+             INSERT INTO repos VALUES (1, 'api');
+             INSERT INTO worktrees VALUES (1, '/code/api-feat');
+             INSERT INTO session_files VALUES ('a', '/code/api-feat/src/lib.rs');
+             INSERT INTO sessions VALUES
+                ('a', 1, 1, 'feat', NULL, 'add paging', 'claude', unixepoch() * 1000, 4),
+                ('b', 1, NULL, 'main', 'Fix login', NULL, 'codex', 0, NULL);",
+        )
+        .unwrap();
+        let uuids = |input: &str| -> Vec<String> {
+            let filter = parse(input).unwrap().to_sql(1);
+            let sql = format!(
+                "SELECT s.uuid FROM sessions s JOIN repos r ON r.id = s.repo_id WHERE {} ORDER BY s.uuid",
+                filter.clause
+            );
+            let mut stmt = conn.prepare(&sql).unwrap();
+            stmt.query_map(rusqlite::params_from_iter(&filter.params), |row| row.get(0))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        assert_eq!(uuids("wt:feat file:lib.rs age:1h msgs:>3"), vec!["a"]);
+        assert_eq!(uuids("-login provider:claude|codex"), vec!["a"]);
+        assert_eq!(uuids("-msgs:>3 age:>1d"), vec!["b"]);
+        assert_eq!(uuids("repo:API"), vec!["a", "b"]);
+    }
+}
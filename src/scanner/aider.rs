@@ -0,0 +1,474 @@
+use super::Session;
+use super::claude::{FileCheckpoint, Role, TextKind, TranscriptText, Turn, file_stamp};
+use super::git;
+use super::provider::{Provider, ProviderStyle, shell_quote};
+use super::timestamp;
+use ratatui::style::Color;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const NAME: &str = "aider";
+
+const CHAT_HISTORY: &str = ".aider.chat.history.md";
+const INPUT_HISTORY: &str = ".aider.input.history";
+const SESSION_HEADER: &str = "# aider chat started at ";
+
+/// Aider, which appends every chat to `.aider.chat.history.md` in the
+/// worktree it runs in. Each `# aider chat started at` header begins a session.
+pub struct AiderProvider;
+
+impl Provider for AiderProvider {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn display_name(&self) -> &str {
+        "Aider"
+    }
+
+    fn session_files(&self, projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        Ok(projects
+            .iter()
+            .map(|p| p.join(CHAT_HISTORY))
+            .filter(|p| p.is_file())
+            .collect())
+    }
+
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        parse_history(path, previous)
+    }
+
+    fn read_turns(
+        &self,
+        path: &Path,
+        uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>> {
+        let chat = read_chats(path)?
+            .into_iter()
+            .find(|c| c.uuid == uuid)
+            .ok_or("Chat no longer in the history file")?;
+        let skip = chat.turns.len().saturating_sub(max_turns);
+        Ok(chat.turns.into_iter().skip(skip).collect())
+    }
+
+    /// Aider can't resume a chat, so this reopens it with the model and files
+    /// the chat ended with
+    fn resume_command(&self, uuid: &str, path: Option<&Path>, _home: Option<&Path>) -> String {
+        let chat = path
+            .and_then(|p| read_chats(p).ok())
+            .and_then(|chats| chats.into_iter().find(|c| c.uuid == uuid));
+        let mut command = "aider".to_string();
+        if let Some(chat) = chat {
+            if let Some(model) = &chat.model {
+                command.push_str(&format!(" --model {}", shell_quote(model)));
+            }
+            for file in &chat.files {
+                command.push(' ');
+                command.push_str(&shell_quote(file));
+            }
+        }
+        command
+    }
+
+    fn style(&self) -> ProviderStyle {
+        ProviderStyle {
+            color: Color::Rgb(80, 200, 120),    // Green
+            dim_color: Color::Rgb(50, 140, 80), // Darker green
+        }
+    }
+}
+
+/// One chat of a history file
+struct Chat {
+    uuid: String,
+    /// Local time from the header, e.g. `2024-05-20 10:15:32`, with
+    /// optional fractional seconds
+    started: String,
+    turns: Vec<Turn>,
+    model: Option<String>,
+    /// Files in the chat when it ended, in the order they were added
+    files: Vec<String>,
+    /// Files aider applied edits to, as written in the history
+    edited: BTreeSet<String>,
+}
+
+/// Split a chat history into chats. Prompts are the `#### ` lines (one
+/// prompt may span several), tool output the `> ` lines, and anything else
+/// is the reply. Chats without a prompt are left out.
+fn read_chats(path: &Path) -> Result<Vec<Chat>, Box<dyn Error>> {
+    let dir = path.parent().ok_or("Invalid history path")?;
+    let content = fs::read_to_string(path)?;
+
+    let mut chats: Vec<Chat> = Vec::new();
+    let mut reply = String::new();
+    let mut in_prompt = false;
+    for line in content.lines() {
+        if let Some(started) = line.strip_prefix(SESSION_HEADER) {
+            if let Some(chat) = chats.last_mut() {
+                flush_reply(chat, &mut reply);
+            }
+            let started = started.trim().to_string();
+            chats.push(Chat {
+                uuid: session_uuid(dir, &started),
+                started,
+                turns: Vec::new(),
+                model: None,
+                files: Vec::new(),
+                edited: BTreeSet::new(),
+            });
+            in_prompt = false;
+            continue;
+        }
+        let Some(chat) = chats.last_mut() else {
+            continue;
+        };
+
+        if let Some(prompt) = line.strip_prefix("#### ") {
+            flush_reply(chat, &mut reply);
+            let prompt = prompt.trim_end();
+            match chat.turns.last_mut() {
+                Some(turn) if in_prompt => {
+                    turn.text.push('\n');
+                    turn.text.push_str(prompt);
+                }
+                _ => chat.turns.push(Turn {
+                    role: Role::User,
+                    text: prompt.to_string(),
+                }),
+            }
+            in_prompt = true;
+            continue;
+        }
+        in_prompt = false;
+
+        if let Some(output) = line.strip_prefix("> ").or((line == ">").then_some("")) {
+            flush_reply(chat, &mut reply);
+            let output = output.trim();
+            if let Some(model) = output
+                .strip_prefix("Main model: ")
+                .or_else(|| output.strip_prefix("Model: "))
+            {
+                let model = model.split(" with ").next().unwrap_or(model);
+                chat.model = Some(model.to_string());
+            } else if let Some(file) = output
+                .strip_prefix("Added ")
+                .and_then(|f| f.strip_suffix(" to the chat."))
+                .or_else(|| {
+                    output
+                        .strip_prefix("Added ")
+                        .and_then(|f| f.strip_suffix(" to the chat"))
+                })
+            {
+                if !chat.files.iter().any(|f| f == file) {
+                    chat.files.push(file.to_string());
+                }
+            } else if let Some(file) = output
+                .strip_prefix("Removed ")
+                .and_then(|f| f.trim_end_matches('.').strip_suffix(" from the chat"))
+            {
+                chat.files.retain(|f| f != file);
+            } else if let Some(file) = output.strip_prefix("Applied edit to ") {
+                chat.edited.insert(file.to_string());
+                chat.turns.push(Turn {
+                    role: Role::Tool,
+                    text: format!("Edit: {}", file),
+                });
+            } else if output.starts_with("Commit ") {
+                chat.turns.push(Turn {
+                    role: Role::Tool,
+                    text: output.to_string(),
+                });
+            }
+            continue;
+        }
+
+        reply.push_str(line);
+        reply.push('\n');
+    }
+    if let Some(chat) = chats.last_mut() {
+        flush_reply(chat, &mut reply);
+    }
+
+    chats.retain(|c| c.turns.iter().any(|t| t.role == Role::User));
+    Ok(chats)
+}
+
+fn flush_reply(chat: &mut Chat, reply: &mut String) {
+    let text = reply.trim();
+    if !text.is_empty() {
+        chat.turns.push(Turn {
+            role: Role::Assistant,
+            text: text.to_string(),
+        });
+    }
+    reply.clear();
+}
+
+/// Parse every chat of a history file into a Session.
+///
+/// The cwd is the worktree holding the file. Aider doesn't record the
+/// branch, so a chat takes the one checked out there when first scanned.
+/// A chat was last active at its last prompt in `.aider.input.history`, or
+/// for the newest chat when the history file was last written.
+/// `first_prompt` skips `/` commands when the chat has any other prompt.
+fn parse_history(path: &Path, previous: &[Session]) -> Result<Vec<Session>, Box<dyn Error>> {
+    let (size, modified) = file_stamp(path)?;
+    let dir = path.parent().ok_or("Invalid history path")?;
+    let chats = read_chats(path)?;
+
+    let input_times = fs::read_to_string(dir.join(INPUT_HISTORY))
+        .map(|input| {
+            input
+                .lines()
+                .filter_map(|line| line.strip_prefix("# "))
+                .map(|time| time.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let starts: Vec<Option<i64>> = chats
+        .iter()
+        .map(|c| timestamp::parse_ms(&c.started, true))
+        .collect();
+    let inputs: Vec<Option<i64>> = input_times
+        .iter()
+        .map(|time| timestamp::parse_ms(time, true))
+        .collect();
+
+    let mut sessions = Vec::new();
+    for (i, chat) in chats.iter().enumerate() {
+        let start = starts[i].unwrap_or(modified);
+        let last_active = if i + 1 == chats.len() {
+            modified
+        } else {
+            let end = starts[i + 1].unwrap_or(i64::MAX);
+            inputs
+                .iter()
+                .flatten()
+                .filter(|t| (start..end).contains(*t))
+                .max()
+                .copied()
+                .unwrap_or(start)
+        };
+
+        let prompts: Vec<&str> = chat
+            .turns
+            .iter()
+            .filter(|t| t.role == Role::User)
+            .map(|t| t.text.as_str())
+            .collect();
+        let first_prompt = prompts
+            .iter()
+            .find(|p| !p.starts_with('/'))
+            .or(prompts.first())
+            .map(|p| p.to_string());
+        let git_branch = previous
+            .iter()
+            .find(|p| p.uuid == chat.uuid)
+            .and_then(|p| p.git_branch.clone())
+            .or_else(|| git::head_branch(dir));
+
+        let transcript = chat
+            .turns
+            .iter()
+            .filter_map(|turn| {
+                let kind = match turn.role {
+                    Role::User => TextKind::User,
+                    Role::Assistant => TextKind::Assistant,
+                    Role::Tool => return None,
+                };
+                Some(TranscriptText {
+                    kind,
+                    text: turn.text.clone(),
+                })
+            })
+            .collect();
+
+        sessions.push(Session {
+            uuid: chat.uuid.clone(),
+            project_path: dir.to_string_lossy().to_string(),
+            git_branch,
+            summary: None,
+            first_prompt,
+            modified: last_active,
+            message_count: Some(prompts.len() as i64),
+            provider: NAME.to_string(),
+            home: None,
+            checkpoint: Some(FileCheckpoint {
+                path: path.to_path_buf(),
+                size,
+                modified,
+                offset: size,
+            }),
+            transcript,
+            usage: Vec::new(),
+            files: chat
+                .edited
+                .iter()
+                .map(|f| dir.join(f).to_string_lossy().to_string())
+                .collect(),
+            boundary_ids: Vec::new(),
+            links: Vec::new(),
+            transcript_resumed: false,
+        });
+    }
+    Ok(sessions)
+}
+
+/// A stable id for the chat started at `started` in the worktree `dir`
+fn session_uuid(dir: &Path, started: &str) -> String {
+    let hash = Sha256::digest(format!("{}\n{}", dir.to_string_lossy(), started).as_bytes());
+    format!("aider-{}", &format!("{:x}", hash)[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = "\
+# aider chat started at 2024-05-20 10:00:00
+
+> Aider v0.82.0
+> Main model: sonnet with diff edit format, infinite output
+> Added src/db.rs to the chat.
+> Added notes.md to the chat.
+
+#### /drop notes.md
+
+> Removed notes.md from the chat
+
+#### fix the failing
+#### migration test
+
+The test expects the old column.
+
+> Applied edit to src/db.rs
+> Commit 1f2e3d4 fix: Expect the new column
+
+# aider chat started at 2024-05-20 11:00:00
+
+> Aider v0.82.0
+
+# aider chat started at 2024-05-20 12:00:00.5
+
+#### what changed?
+
+Only src/db.rs.
+";
+
+    const INPUT: &str = "\
+# 2024-05-20 10:01:00.000
++/drop notes.md
+
+# 2024-05-20 10:05:00.123
++fix the failing
++migration test
+
+# 2024-05-20 12:00:10.000
++what changed?
+";
+
+    /// A worktree with `HISTORY` and `INPUT` and `main` checked out
+    fn worktree(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ws-aider-{}-{}", std::process::id(), name));
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(dir.join(CHAT_HISTORY), HISTORY).unwrap();
+        fs::write(dir.join(INPUT_HISTORY), INPUT).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_each_chat_with_a_prompt() {
+        let dir = worktree("parse");
+        let path = dir.join(CHAT_HISTORY);
+        let sessions = parse_history(&path, &[]).unwrap();
+        assert_eq!(sessions.len(), 2);
+        let (first, last) = (&sessions[0], &sessions[1]);
+
+        assert_eq!(first.uuid, session_uuid(&dir, "2024-05-20 10:00:00"));
+        assert!(first.uuid.starts_with("aider-") && first.uuid.len() == 22);
+        assert_eq!(first.project_path, dir.to_string_lossy());
+        assert_eq!(first.git_branch.as_deref(), Some("main"));
+        assert_eq!(
+            first.first_prompt.as_deref(),
+            Some("fix the failing\nmigration test")
+        );
+        assert_eq!(first.message_count, Some(2));
+        let texts: Vec<&str> = first.transcript.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            [
+                "/drop notes.md",
+                "fix the failing\nmigration test",
+                "The test expects the old column."
+            ]
+        );
+        assert_eq!(
+            first.files.iter().collect::<Vec<_>>(),
+            [&dir.join("src/db.rs").to_string_lossy().to_string()]
+        );
+
+        // Last active at its last input; the newest chat when the file was written
+        assert_eq!(
+            first.modified,
+            timestamp::parse_ms("2024-05-20 10:05:00.123", true).unwrap()
+        );
+        let (_, modified) = file_stamp(&path).unwrap();
+        assert_eq!(last.modified, modified);
+        assert_eq!(last.first_prompt.as_deref(), Some("what changed?"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_branch_a_chat_was_first_seen_on() {
+        let dir = worktree("branch");
+        let path = dir.join(CHAT_HISTORY);
+        let mut previous = parse_history(&path, &[]).unwrap();
+        previous[0].git_branch = Some("feature".to_string());
+        let sessions = parse_history(&path, &previous).unwrap();
+        assert_eq!(sessions[0].git_branch.as_deref(), Some("feature"));
+        assert_eq!(sessions[1].git_branch.as_deref(), Some("main"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reopens_a_chat_with_its_model_and_files() {
+        let dir = worktree("resume");
+        let path = dir.join(CHAT_HISTORY);
+        let uuid = session_uuid(&dir, "2024-05-20 10:00:00");
+        assert_eq!(
+            AiderProvider.resume_command(&uuid, Some(&path), None),
+            "aider --model sonnet src/db.rs"
+        );
+        assert_eq!(
+            AiderProvider.resume_command(
+                &session_uuid(&dir, "2024-05-20 12:00:00.5"),
+                Some(&path),
+                None
+            ),
+            "aider"
+        );
+
+        let turns = AiderProvider.read_turns(&path, &uuid, 2).unwrap();
+        let turns: Vec<(Role, &str)> = turns.iter().map(|t| (t.role, t.text.as_str())).collect();
+        assert_eq!(
+            turns,
+            [
+                (Role::Tool, "Edit: src/db.rs"),
+                (Role::Tool, "Commit 1f2e3d4 fix: Expect the new column"),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
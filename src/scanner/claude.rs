@@ -1,8 +1,12 @@
-use super::SessionProvider;
+use super::provider::{Provider, ProviderStyle, agent_homes, home_of, with_home};
+use super::timestamp;
+use crate::migrate;
+use ratatui::style::Color;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct Session {
@@ -13,117 +17,583 @@ pub struct Session {
     pub first_prompt: Option<String>,
     pub modified: i64,
     pub message_count: Option<i64>,
-    pub provider: SessionProvider,
+    /// Name of the `Provider` that parsed it
+    pub provider: String,
+    /// Agent home the session file is under, for agents that can have several
+    pub home: Option<PathBuf>,
+    pub checkpoint: Option<FileCheckpoint>,
+    /// Searchable text parsed in this pass, in transcript order
+    pub transcript: Vec<TranscriptText>,
+    /// Tokens used by each reply read in this pass
+    pub usage: Vec<MessageUsage>,
+    /// Files written by tool calls in this pass
+    pub files: BTreeSet<String>,
+    /// Uuids of the messages in this pass that a later session can continue
+    /// from: the file's first message, each compaction boundary, and the
+    /// last message read
+    pub boundary_ids: Vec<String>,
+    /// References to earlier sessions found in this pass
+    pub links: Vec<SessionLink>,
+    /// True if `transcript`, `usage`, `files`, `boundary_ids` and `links`
+    /// continue what an earlier parse already stored, false if they replace them
+    pub transcript_resumed: bool,
 }
 
-pub fn scan_sessions() -> Result<Vec<Session>, Box<dyn Error>> {
-    let claude_dir = dirs::home_dir()
-        .ok_or("Could not find home directory")?
-        .join(".claude")
-        .join("projects");
+/// A reference from a session to the one it continues
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionLink {
+    pub kind: LinkKind,
+    /// A message uuid, or for a sidechain the parent session's uuid
+    pub target: String,
+}
+
+/// How a session continues an earlier one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// Resumed or continued: its first message replies to a message of another session
+    Resume,
+    /// Started from a compaction boundary whose logical parent is in another session
+    Compact,
+    /// A subagent run of another session
+    Sidechain,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Resume => "resume",
+            LinkKind::Compact => "compact",
+            LinkKind::Sidechain => "sidechain",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "resume" => Some(LinkKind::Resume),
+            "compact" => Some(LinkKind::Compact),
+            "sidechain" => Some(LinkKind::Sidechain),
+            _ => None,
+        }
+    }
+}
+
+/// Tokens billed for one reply. Replies are stored by id, so a reply read
+/// again (as when a pass resumes in the middle of it) replaces the earlier count.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageUsage {
+    /// Unique within the session
+    pub id: String,
+    pub model: String,
+    /// When the reply was written, in ms since epoch
+    pub timestamp: i64,
+    pub tokens: TokenUsage,
+}
+
+/// Tokens billed for one model. `input` excludes cached input.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub input: i64,
+    pub output: i64,
+    pub cache_creation: i64,
+    pub cache_read: i64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> i64 {
+        self.input + self.output + self.cache_creation + self.cache_read
+    }
+
+    pub fn add(&mut self, other: &TokenUsage) {
+        self.input += other.input;
+        self.output += other.output;
+        self.cache_creation += other.cache_creation;
+        self.cache_read += other.cache_read;
+    }
+
+    /// A Claude `message.usage` object
+    fn from_claude(usage: &serde_json::Value) -> Self {
+        let count = |key: &str| usage.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+        TokenUsage {
+            input: count("input_tokens"),
+            output: count("output_tokens"),
+            cache_creation: count("cache_creation_input_tokens"),
+            cache_read: count("cache_read_input_tokens"),
+        }
+    }
+}
+
+/// A user prompt, assistant reply or summary, indexed for full-text search
+#[derive(Debug, Clone)]
+pub struct TranscriptText {
+    pub kind: TextKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    User,
+    Assistant,
+    Summary,
+}
+
+impl TextKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TextKind::User => "user",
+            TextKind::Assistant => "assistant",
+            TextKind::Summary => "summary",
+        }
+    }
+}
+
+/// One turn of a session transcript, as shown in the TUI preview
+#[derive(Debug, Clone)]
+pub struct Turn {
+    pub role: Role,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    User,
+    Assistant,
+    /// A tool call, collapsed to one line
+    Tool,
+}
+
+/// Text of a message `content`: either a plain string or the `text` blocks of
+/// a block array (tool calls, tool results and images are left out)
+fn content_text(content: &serde_json::Value) -> Option<String> {
+    let text = match content {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(blocks) => blocks
+            .iter()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// What the user typed in a `type: "user"` line, or `None` if the line isn't
+/// a turn of theirs: tool results, meta lines, compaction summaries,
+/// transcript-only lines, local command output and interruption notices are
+/// written as user lines too. Slash commands are
+/// rendered as typed (`/review src/db.rs`) instead of as their XML wrapper.
+fn human_text(value: &serde_json::Value) -> Option<String> {
+    let flagged = |key: &str| value.get(key).and_then(|m| m.as_bool()) == Some(true);
+    if flagged("isMeta") || flagged("isCompactSummary") || flagged("isVisibleInTranscriptOnly") {
+        return None;
+    }
+    let content = value.get("message").and_then(|m| m.get("content"))?;
+    if let Some(blocks) = content.as_array()
+        && blocks
+            .iter()
+            .any(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+    {
+        return None;
+    }
+    let text = content_text(content)?;
+    let text = text.trim();
+
+    const NOT_TYPED: [&str; 6] = [
+        "<local-command-caveat>",
+        "<local-command-stdout>",
+        "<local-command-stderr>",
+        "<bash-stdout>",
+        "<bash-stderr>",
+        "[Request interrupted by user",
+    ];
+    if NOT_TYPED.iter().any(|prefix| text.starts_with(prefix)) {
+        return None;
+    }
+    if let Some(name) = tag_text(text, "command-name") {
+        let name = name.trim_start_matches('/');
+        return Some(
+            match tag_text(text, "command-args").filter(|a| !a.is_empty()) {
+                Some(args) => format!("/{} {}", name, args),
+                None => format!("/{}", name),
+            },
+        );
+    }
+    if let Some(command) = tag_text(text, "bash-input") {
+        return Some(format!("! {}", command));
+    }
+    Some(text.to_string())
+}
+
+/// Trimmed contents of the first `<tag>…</tag>` in `text`
+fn tag_text<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let start = text.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + text[start..].find(&format!("</{}>", tag))?;
+    Some(text[start..end].trim())
+}
+
+/// How far a session file has been parsed, so later scans can skip it or
+/// pick up where they left off
+#[derive(Debug, Clone)]
+pub struct FileCheckpoint {
+    pub path: PathBuf,
+    pub size: i64,
+    pub modified: i64, // file mtime (ms); `Session::modified` unless the file holds several sessions
+    pub offset: i64,   // byte after the last complete line parsed
+}
+
+pub const NAME: &str = "claude";
+
+/// Environment variable that moves Claude's home away from `~/.claude`
+const CONFIG_DIR_VAR: &str = "CLAUDE_CONFIG_DIR";
+const DEFAULT_HOME: &str = ".claude";
+
+/// Claude Code, which keeps one JSONL file per session under the project
+/// directory it ran in, in each of its homes
+pub struct ClaudeProvider {
+    homes: Vec<PathBuf>,
+}
+
+impl ClaudeProvider {
+    /// `$CLAUDE_CONFIG_DIR` (or `~/.claude`) and the `extra` homes
+    pub fn new(extra: &[String]) -> Self {
+        ClaudeProvider {
+            homes: agent_homes(CONFIG_DIR_VAR, DEFAULT_HOME, extra),
+        }
+    }
+}
+
+impl Provider for ClaudeProvider {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn display_name(&self) -> &str {
+        "Claude"
+    }
+
+    fn session_files(&self, _projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for home in &self.homes {
+            files.extend(session_files(home)?);
+        }
+        Ok(files)
+    }
+
+    /// Session files are named by their id
+    fn session_id(&self, path: &Path) -> Option<String> {
+        Some(path.file_stem()?.to_str()?.to_string())
+    }
+
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        let mut session = parse_jsonl_session(path, previous.first())?;
+        session.home = home_of(&self.homes, path).cloned();
+        Ok(vec![session])
+    }
+
+    fn read_turns(
+        &self,
+        path: &Path,
+        _uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>> {
+        read_turns(path, max_turns)
+    }
+
+    fn resume_command(&self, uuid: &str, _path: Option<&Path>, home: Option<&Path>) -> String {
+        with_home(
+            format!("claude --resume {}", uuid),
+            CONFIG_DIR_VAR,
+            DEFAULT_HOME,
+            home,
+        )
+    }
+
+    /// Claude only resumes sessions stored under the current directory's
+    /// project. Sessions from before homes were recorded are in the first.
+    fn migrate_session(
+        &self,
+        uuid: &str,
+        home: Option<&Path>,
+        source: &Path,
+        target: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let home = home
+            .or(self.homes.first().map(PathBuf::as_path))
+            .ok_or("Could not find Claude's home directory")?;
+        migrate::migrate_session(uuid, home, source, target)
+    }
+
+    fn style(&self) -> ProviderStyle {
+        ProviderStyle {
+            color: Color::Rgb(255, 165, 0),     // Orange
+            dim_color: Color::Rgb(200, 130, 0), // Darker orange
+        }
+    }
+}
+
+/// List the session files in a Claude home (`<home>/projects/*/*.jsonl`)
+pub fn session_files(home: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let claude_dir = home.join("projects");
 
     if !claude_dir.exists() {
         return Ok(Vec::new());
     }
 
-    let mut sessions = Vec::new();
-
-    // Glob ~/.claude/projects/*/*.jsonl
     let pattern = claude_dir.join("*").join("*.jsonl");
     let pattern_str = pattern.to_string_lossy();
 
-    for entry in glob::glob(&pattern_str)? {
-        if let Ok(path) = entry {
-            match parse_jsonl_session(&path) {
-                Ok(session) => sessions.push(session),
-                Err(e) => {
-                    eprintln!("Warning: failed to parse {:?}: {}", path, e);
-                }
-            }
-        }
-    }
+    Ok(glob::glob(&pattern_str)?.flatten().collect())
+}
 
-    Ok(sessions)
+/// How long (ms) a file must go unmodified before an unterminated last line
+/// counts as complete
+const SETTLE_MS: i64 = 5_000;
+
+/// Size and mtime (ms) of a session file, compared against its checkpoint
+pub fn file_stamp(path: &Path) -> Result<(i64, i64), Box<dyn Error>> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_millis() as i64;
+    Ok((metadata.len() as i64, modified))
 }
 
 /// Parse a single JSONL session file into a Session.
 ///
 /// Extracts metadata by reading lines one at a time:
 /// - `cwd` and `gitBranch` from the first line that has them.
-/// - `first_prompt` from the first `type: "user"` line with a string `message.content`.
+/// - `first_prompt` from the first user turn (see `human_text`).
 /// - `summary` from a `type: "summary"` line (if present).
-/// - `message_count` as the count of `type: "user"` lines.
+/// - `message_count` as the count of user turns.
+/// - `transcript` from user and assistant message text and summaries.
+/// - `usage` from the `message.model` and `message.usage` of assistant lines,
+///   at their `timestamp`.
+/// - `files` from the Edit, Write, MultiEdit and NotebookEdit tool calls.
+/// - `boundary_ids` from the `uuid` of the first line of the file, of
+///   `compact_boundary` lines and of the last line read, and `links` from
+///   `parentUuid`, `logicalParentUuid` and summary `leafUuid` pointing
+///   outside the file and from the `sessionId` of sidechain lines written for
+///   another session.
 /// - `modified` from file mtime (reliable proxy since Claude writes as the session progresses).
-fn parse_jsonl_session(path: &PathBuf) -> Result<Session, Box<dyn Error>> {
+///
+/// If `previous` is the result of an earlier parse and the file has only grown
+/// since, parsing resumes from its checkpoint and builds on its metadata.
+pub fn parse_jsonl_session(
+    path: &Path,
+    previous: Option<&Session>,
+) -> Result<Session, Box<dyn Error>> {
     let uuid = path
         .file_stem()
         .and_then(|s| s.to_str())
         .ok_or("Invalid filename")?
         .to_string();
 
-    let modified = fs::metadata(path)?
-        .modified()?
-        .duration_since(std::time::UNIX_EPOCH)?
-        .as_millis() as i64;
+    let (size, modified) = file_stamp(path)?;
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    // Resume only if the file was appended to; a shrunk file was rewritten
+    let previous = previous.filter(|prev| {
+        prev.checkpoint
+            .as_ref()
+            .is_some_and(|cp| cp.size <= size && cp.offset <= size)
+    });
 
-    let mut cwd: Option<String> = None;
-    let mut git_branch: Option<String> = None;
-    let mut first_prompt: Option<String> = None;
-    let mut summary: Option<String> = None;
-    let mut message_count: i64 = 0;
+    let mut cwd: Option<String> = previous
+        .map(|p| p.project_path.clone())
+        .filter(|p| !p.is_empty());
+    let mut git_branch: Option<String> = previous.and_then(|p| p.git_branch.clone());
+    let mut first_prompt: Option<String> = previous.and_then(|p| p.first_prompt.clone());
+    let mut summary: Option<String> = previous.and_then(|p| p.summary.clone());
+    let mut message_count: i64 = previous.and_then(|p| p.message_count).unwrap_or(0);
+    let mut offset: i64 = previous
+        .and_then(|p| p.checkpoint.as_ref())
+        .map_or(0, |cp| cp.offset);
+    let mut transcript = Vec::new();
+    // Each content block of a message is its own line repeating the message's
+    // usage, so usage is keyed by message id and the last line wins
+    let mut usage: HashMap<String, MessageUsage> = HashMap::new();
+    let mut files = BTreeSet::new();
+    let mut boundary_ids = Vec::new();
+    let mut last_id: Option<String> = None;
+    let from_start = offset == 0;
+    let mut seen_ids: HashSet<String> = HashSet::new();
+    let mut links: Vec<SessionLink> = Vec::new();
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(l) => l,
-            Err(_) => continue,
-        };
+    // A last line without a newline may still be being written. It is read
+    // anyway on a full parse, or once the file has been left alone for a
+    // while, as an unchanged file is never parsed again.
+    let settled = offset == 0
+        || std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .is_ok_and(|now| now.as_millis() as i64 - modified > SETTLE_MS);
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
 
-        let value: serde_json::Value = match serde_json::from_str(&line) {
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        let parsed = serde_json::from_slice::<serde_json::Value>(&line);
+        // Stop at a trailing line still being written; the next scan picks it up
+        if line.last() != Some(&b'\n') && !(settled && parsed.is_ok()) {
+            break;
+        }
+        offset += read as i64;
+
+        let value = match parsed {
             Ok(v) => v,
             Err(_) => continue,
         };
 
         // Extract cwd and gitBranch from the first line that has them.
-        if cwd.is_none() {
-            if let Some(c) = value.get("cwd").and_then(|v| v.as_str()) {
-                cwd = Some(c.to_string());
+        if cwd.is_none()
+            && let Some(c) = value.get("cwd").and_then(|v| v.as_str())
+        {
+            cwd = Some(c.to_string());
+        }
+        if git_branch.is_none()
+            && let Some(b) = value.get("gitBranch").and_then(|v| v.as_str())
+        {
+            git_branch = Some(b.to_string());
+        }
+
+        // Lineage: references to messages not seen earlier in this pass may be
+        // in another session; ones that turn out to be in this file (read by
+        // an earlier pass) are ignored when links are resolved
+        let field = |key: &str| value.get(key).and_then(|v| v.as_str());
+        let mut link = |kind: LinkKind, target: &str| {
+            if !seen_ids.contains(target) && !links.iter().any(|l| l.target == target) {
+                links.push(SessionLink {
+                    kind,
+                    target: target.to_string(),
+                });
             }
+        };
+        if let Some(parent) = field("parentUuid") {
+            link(LinkKind::Resume, parent);
+        }
+        if let Some(parent) = field("logicalParentUuid") {
+            link(LinkKind::Compact, parent);
+        }
+        if field("type") == Some("summary")
+            && let Some(leaf) = field("leafUuid")
+        {
+            link(LinkKind::Resume, leaf);
         }
-        if git_branch.is_none() {
-            if let Some(b) = value.get("gitBranch").and_then(|v| v.as_str()) {
-                git_branch = Some(b.to_string());
+        if value.get("isSidechain").and_then(|v| v.as_bool()) == Some(true)
+            && let Some(session_id) = field("sessionId")
+            && session_id != uuid
+        {
+            link(LinkKind::Sidechain, session_id);
+        }
+        if let Some(id) = field("uuid")
+            && seen_ids.insert(id.to_string())
+        {
+            if (from_start && last_id.is_none()) || field("subtype") == Some("compact_boundary") {
+                boundary_ids.push(id.to_string());
             }
+            last_id = Some(id.to_string());
         }
 
         let line_type = value.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        let message_text = || {
+            value
+                .get("message")
+                .and_then(|m| m.get("content"))
+                .and_then(content_text)
+        };
 
         match line_type {
             "user" => {
+                // Subagent prompts written into the parent's own file aren't the user's
+                let sidechain = value.get("isSidechain").and_then(|v| v.as_bool()) == Some(true)
+                    && field("sessionId").is_none_or(|id| id == uuid);
+                let Some(text) = human_text(&value).filter(|_| !sidechain) else {
+                    continue;
+                };
                 message_count += 1;
-
-                // Extract first prompt from first user message with string content.
                 if first_prompt.is_none() {
-                    if let Some(content) = value
-                        .get("message")
-                        .and_then(|m| m.get("content"))
-                        .and_then(|c| c.as_str())
+                    first_prompt = Some(text.clone());
+                }
+                transcript.push(TranscriptText {
+                    kind: TextKind::User,
+                    text,
+                });
+            }
+            "assistant" => {
+                if let Some(text) = message_text() {
+                    transcript.push(TranscriptText {
+                        kind: TextKind::Assistant,
+                        text,
+                    });
+                }
+
+                let message = value.get("message");
+                let blocks = message
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_array())
+                    .into_iter()
+                    .flatten();
+                for block in blocks {
+                    if block.get("type").and_then(|t| t.as_str()) == Some("tool_use")
+                        && let Some(name) = block.get("name").and_then(|n| n.as_str())
+                        && let Some(path) = block.get("input").and_then(|i| edited_file(name, i))
                     {
-                        first_prompt = Some(content.to_string());
+                        files.insert(path.to_string());
                     }
                 }
+
+                let model = message
+                    .and_then(|m| m.get("model"))
+                    .and_then(|m| m.as_str())
+                    .filter(|m| *m != "<synthetic>");
+                if let (Some(model), Some(tokens)) = (model, message.and_then(|m| m.get("usage")))
+                    && let Some(id) = message
+                        .and_then(|m| m.get("id"))
+                        .and_then(|id| id.as_str())
+                        .or(field("uuid"))
+                {
+                    usage.insert(
+                        id.to_string(),
+                        MessageUsage {
+                            id: id.to_string(),
+                            model: model.to_string(),
+                            timestamp: field("timestamp")
+                                .and_then(|t| timestamp::parse_ms(t, false))
+                                .unwrap_or(modified),
+                            tokens: TokenUsage::from_claude(tokens),
+                        },
+                    );
+                }
             }
             "summary" => {
                 if let Some(s) = value.get("summary").and_then(|v| v.as_str()) {
                     summary = Some(s.to_string());
+                    transcript.push(TranscriptText {
+                        kind: TextKind::Summary,
+                        text: s.to_string(),
+                    });
                 }
             }
             _ => {}
         }
     }
 
+    if let Some(id) = last_id
+        && boundary_ids.last() != Some(&id)
+    {
+        boundary_ids.push(id);
+    }
+
     Ok(Session {
         uuid,
         project_path: cwd.unwrap_or_default(),
@@ -132,6 +602,440 @@ fn parse_jsonl_session(path: &PathBuf) -> Result<Session, Box<dyn Error>> {
         first_prompt,
         modified,
         message_count: Some(message_count),
-        provider: SessionProvider::Claude,
+        provider: NAME.to_string(),
+        home: None,
+        checkpoint: Some(FileCheckpoint {
+            path: path.to_path_buf(),
+            size,
+            modified,
+            offset,
+        }),
+        transcript,
+        usage: usage.into_values().collect(),
+        files,
+        boundary_ids,
+        links,
+        transcript_resumed: previous.is_some(),
     })
 }
+
+/// Read the last `max_turns` user, assistant and tool-call turns of a session
+/// file for the preview. User lines that aren't typed turns are left out
+/// (see `human_text`), as are tool results.
+pub fn read_turns(path: &Path, max_turns: usize) -> Result<Vec<Turn>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut turns = VecDeque::new();
+    let mut line = Vec::new();
+
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let value: Option<serde_json::Value> = serde_json::from_slice(&line).ok();
+        line.clear();
+        let Some(value) = value else {
+            continue;
+        };
+        let line_turns: Vec<Turn> = match value.get("type").and_then(|t| t.as_str()) {
+            Some("user") => human_text(&value)
+                .map(|text| Turn {
+                    role: Role::User,
+                    text,
+                })
+                .into_iter()
+                .collect(),
+            Some("assistant") => {
+                let Some(content) = value.get("message").and_then(|m| m.get("content")) else {
+                    continue;
+                };
+                // Keep text and tool calls in the order the blocks appear
+                let blocks = match content {
+                    serde_json::Value::Array(blocks) => blocks.as_slice(),
+                    _ => std::slice::from_ref(content),
+                };
+                blocks.iter().filter_map(assistant_turn).collect()
+            }
+            _ => continue,
+        };
+        for turn in line_turns {
+            if turns.len() == max_turns {
+                turns.pop_front();
+            }
+            turns.push_back(turn);
+        }
+    }
+
+    Ok(turns.into())
+}
+
+/// A text or tool-call block of an assistant message as a preview turn
+fn assistant_turn(block: &serde_json::Value) -> Option<Turn> {
+    let turn = match block.get("type").and_then(|t| t.as_str()) {
+        None => block.as_str().map(|text| Turn {
+            role: Role::Assistant,
+            text: text.to_string(),
+        }),
+        Some("text") => block.get("text").and_then(|t| t.as_str()).map(|text| Turn {
+            role: Role::Assistant,
+            text: text.to_string(),
+        }),
+        Some("tool_use") => Some(Turn {
+            role: Role::Tool,
+            text: tool_summary(
+                block.get("name").and_then(|n| n.as_str()).unwrap_or("tool"),
+                block.get("input").unwrap_or(&serde_json::Value::Null),
+            ),
+        }),
+        _ => None,
+    };
+    turn.filter(|t| !t.text.trim().is_empty())
+}
+
+/// The file an editing tool call writes to
+fn edited_file<'a>(name: &str, input: &'a serde_json::Value) -> Option<&'a str> {
+    match name {
+        "Edit" | "Write" | "MultiEdit" => input.get("file_path")?.as_str(),
+        "NotebookEdit" => input
+            .get("notebook_path")
+            .or_else(|| input.get("file_path"))?
+            .as_str(),
+        _ => None,
+    }
+}
+
+/// A tool call on one line: its name and the most telling input, e.g.
+/// `Bash: cargo test` or `Edit: src/db/mod.rs`
+pub(super) fn tool_summary(name: &str, input: &serde_json::Value) -> String {
+    const KEYS: [&str; 8] = [
+        "command",
+        "file_path",
+        "notebook_path",
+        "path",
+        "pattern",
+        "url",
+        "query",
+        "description",
+    ];
+    let detail = KEYS.iter().find_map(|key| match input.get(key)? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        // Codex shell commands are argv arrays
+        serde_json::Value::Array(args) => Some(
+            args.iter()
+                .filter_map(|a| a.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        _ => None,
+    });
+
+    match detail {
+        Some(detail) => format!("{}: {}", name, detail.lines().next().unwrap_or_default()),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::{Duration, SystemTime};
+
+    /// A session file named `<uuid>.jsonl` in a fresh temp dir
+    fn session_file(name: &str, uuid: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ws-claude-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.jsonl", uuid));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn append(path: &Path, contents: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    /// Backdate the file so its last line counts as settled
+    fn settle(path: &Path) {
+        let old = SystemTime::now() - Duration::from_millis(SETTLE_MS as u64 * 2);
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+    }
+
+    fn cleanup(path: &Path) {
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    fn texts(session: &Session) -> Vec<&str> {
+        session.transcript.iter().map(|t| t.text.as_str()).collect()
+    }
+
+    const PROMPT: &str = r#"{"type":"user","cwd":"/code/api","gitBranch":"main","message":{"role":"user","content":"fix the tests"}}
+"#;
+    const REPLY: &str = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"done"}]}}
+"#;
+
+    #[test]
+    fn resumes_from_the_checkpoint() {
+        let path = session_file("resume", "s1", PROMPT);
+        let first = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(first.uuid, "s1");
+        assert_eq!(first.project_path, "/code/api");
+        assert_eq!(first.message_count, Some(1));
+        assert!(!first.transcript_resumed);
+        let checkpoint = first.checkpoint.as_ref().unwrap();
+        assert_eq!(checkpoint.offset, PROMPT.len() as i64);
+
+        append(&path, REPLY);
+        append(
+            &path,
+            r#"{"type":"user","message":{"role":"user","content":"and the docs"}}
+"#,
+        );
+        let second = parse_jsonl_session(&path, Some(&first)).unwrap();
+        assert!(second.transcript_resumed);
+        assert_eq!(texts(&second), ["done", "and the docs"]);
+        assert_eq!(second.message_count, Some(2));
+        assert_eq!(second.first_prompt.as_deref(), Some("fix the tests"));
+        assert_eq!(second.git_branch.as_deref(), Some("main"));
+        let checkpoint = second.checkpoint.as_ref().unwrap();
+        assert_eq!(checkpoint.offset, checkpoint.size);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn rereads_a_shrunk_file_from_the_start() {
+        let path = session_file("shrunk", "s1", &format!("{}{}", PROMPT, REPLY));
+        let first = parse_jsonl_session(&path, None).unwrap();
+
+        fs::write(&path, PROMPT).unwrap();
+        let second = parse_jsonl_session(&path, Some(&first)).unwrap();
+        assert!(!second.transcript_resumed);
+        assert_eq!(texts(&second), ["fix the tests"]);
+        assert_eq!(second.message_count, Some(1));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn holds_back_an_unterminated_line_until_it_settles() {
+        let path = session_file("settle", "s1", PROMPT);
+        let first = parse_jsonl_session(&path, None).unwrap();
+
+        append(&path, REPLY.trim_end());
+        let recent = parse_jsonl_session(&path, Some(&first)).unwrap();
+        assert!(recent.transcript.is_empty());
+        assert_eq!(
+            recent.checkpoint.as_ref().unwrap().offset,
+            PROMPT.len() as i64
+        );
+
+        settle(&path);
+        let settled = parse_jsonl_session(&path, Some(&recent)).unwrap();
+        assert_eq!(texts(&settled), ["done"]);
+        let checkpoint = settled.checkpoint.as_ref().unwrap();
+        assert_eq!(checkpoint.offset, checkpoint.size);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn a_full_parse_reads_an_unterminated_line() {
+        let path = session_file("full", "s1", &format!("{}{}", PROMPT, REPLY.trim_end()));
+        let session = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(texts(&session), ["fix the tests", "done"]);
+
+        // One still being written is left for the next scan
+        fs::write(&path, format!("{}{{\"type\":\"assi", PROMPT)).unwrap();
+        let session = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(session.checkpoint.unwrap().offset, PROMPT.len() as i64);
+        cleanup(&path);
+    }
+
+    #[test]
+    fn counts_each_reply_once() {
+        let path = session_file(
+            "usage",
+            "s1",
+            r#"{"type":"assistant","uuid":"a1","timestamp":"2024-05-20T10:15:32.120Z","message":{"id":"msg_1","model":"claude-sonnet-4","content":[{"type":"text","text":"looking"}],"usage":{"input_tokens":10,"output_tokens":1,"cache_read_input_tokens":100}}}
+{"type":"assistant","uuid":"a2","timestamp":"2024-05-20T10:15:33Z","message":{"id":"msg_1","model":"claude-sonnet-4","content":[{"type":"tool_use","name":"Bash","input":{}}],"usage":{"input_tokens":10,"output_tokens":25,"cache_read_input_tokens":100}}}
+{"type":"assistant","uuid":"a3","message":{"model":"claude-haiku-4","content":"ok","usage":{"output_tokens":3,"cache_creation_input_tokens":7}}}
+{"type":"assistant","uuid":"a4","message":{"id":"msg_2","model":"<synthetic>","content":"No response requested.","usage":{"output_tokens":0}}}
+"#,
+        );
+        let session = parse_jsonl_session(&path, None).unwrap();
+        let (_, modified) = file_stamp(&path).unwrap();
+        let mut usage = session.usage.clone();
+        usage.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(
+            usage,
+            [
+                MessageUsage {
+                    id: "a3".to_string(),
+                    model: "claude-haiku-4".to_string(),
+                    timestamp: modified,
+                    tokens: TokenUsage {
+                        output: 3,
+                        cache_creation: 7,
+                        ..TokenUsage::default()
+                    },
+                },
+                MessageUsage {
+                    id: "msg_1".to_string(),
+                    model: "claude-sonnet-4".to_string(),
+                    timestamp: 1_716_200_133_000,
+                    tokens: TokenUsage {
+                        input: 10,
+                        output: 25,
+                        cache_creation: 0,
+                        cache_read: 100,
+                    },
+                },
+            ]
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn finds_the_file_an_edit_writes() {
+        let input = serde_json::json!({ "file_path": "/code/api/main.rs", "command": "ls" });
+        for tool in ["Edit", "Write", "MultiEdit", "NotebookEdit"] {
+            assert_eq!(edited_file(tool, &input), Some("/code/api/main.rs"));
+        }
+        assert_eq!(edited_file("Read", &input), None);
+        assert_eq!(edited_file("Bash", &input), None);
+        let notebook = serde_json::json!({ "notebook_path": "/code/nb.ipynb" });
+        assert_eq!(
+            edited_file("NotebookEdit", &notebook),
+            Some("/code/nb.ipynb")
+        );
+        assert_eq!(edited_file("Edit", &serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn collects_the_files_a_session_edits() {
+        let path = session_file(
+            "files",
+            "s1",
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"editing"},{"type":"tool_use","name":"Edit","input":{"file_path":"/code/b.rs"}},{"type":"tool_use","name":"Write","input":{"file_path":"/code/a.rs"}}]}}
+{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/code/c.rs"}},{"type":"tool_use","name":"Edit","input":{"file_path":"/code/b.rs"}}]}}
+{"type":"user","message":{"content":[{"type":"tool_result","content":"/code/d.rs"}]}}
+"#,
+        );
+        let session = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(
+            session.files.into_iter().collect::<Vec<_>>(),
+            ["/code/a.rs", "/code/b.rs"]
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn records_boundaries_and_links_to_other_sessions() {
+        let path = session_file(
+            "lineage",
+            "s2",
+            r#"{"type":"summary","summary":"Fix tests","leafUuid":"x9"}
+{"type":"user","uuid":"u1","parentUuid":"x5","message":{"content":"go on"}}
+{"type":"assistant","uuid":"a1","parentUuid":"u1","message":{"content":"ok"}}
+{"type":"system","subtype":"compact_boundary","uuid":"c1","parentUuid":null,"logicalParentUuid":"y1"}
+{"type":"user","uuid":"u2","parentUuid":"c1","message":{"content":"next"}}
+{"type":"user","uuid":"u3","parentUuid":"u2","isSidechain":true,"sessionId":"p1","message":{"content":"search"}}
+"#,
+        );
+        let session = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(session.boundary_ids, ["u1", "c1", "u3"]);
+        let link = |kind, target: &str| SessionLink {
+            kind,
+            target: target.to_string(),
+        };
+        assert_eq!(
+            session.links,
+            [
+                link(LinkKind::Resume, "x9"),
+                link(LinkKind::Resume, "x5"),
+                link(LinkKind::Compact, "y1"),
+                link(LinkKind::Sidechain, "p1"),
+            ]
+        );
+
+        // A resumed pass has no first message, only its last
+        append(
+            &path,
+            r#"{"type":"assistant","uuid":"a2","parentUuid":"u2","message":{"content":"done"}}
+"#,
+        );
+        let resumed = parse_jsonl_session(&path, Some(&session)).unwrap();
+        assert_eq!(resumed.boundary_ids, ["a2"]);
+        // u2 was read by the earlier pass, which this one can't tell
+        assert_eq!(resumed.links, [link(LinkKind::Resume, "u2")]);
+        cleanup(&path);
+    }
+
+    fn typed(line: &str) -> Option<String> {
+        human_text(&serde_json::from_str(line).unwrap())
+    }
+
+    #[test]
+    fn reads_what_the_user_typed() {
+        assert_eq!(
+            typed(r#"{"message":{"content":"  fix the tests\n"}}"#).as_deref(),
+            Some("fix the tests")
+        );
+        assert_eq!(
+            typed(
+                r#"{"message":{"content":[{"type":"image"},{"type":"text","text":"what is this"}]}}"#
+            )
+            .as_deref(),
+            Some("what is this")
+        );
+        assert_eq!(
+            typed(r#"{"message":{"content":"<command-message>review</command-message>\n<command-name>/review</command-name>\n<command-args>src/db.rs</command-args>"}}"#)
+                .as_deref(),
+            Some("/review src/db.rs")
+        );
+        assert_eq!(
+            typed(r#"{"message":{"content":"<command-name>/clear</command-name>\n<command-args></command-args>"}}"#)
+                .as_deref(),
+            Some("/clear")
+        );
+        assert_eq!(
+            typed(r#"{"message":{"content":"<bash-input>git status</bash-input>"}}"#).as_deref(),
+            Some("! git status")
+        );
+    }
+
+    #[test]
+    fn skips_user_lines_nobody_typed() {
+        for line in [
+            r#"{"isMeta":true,"message":{"content":"Caveat: the messages below"}}"#,
+            r#"{"isCompactSummary":true,"message":{"content":"This session is being continued"}}"#,
+            r#"{"isVisibleInTranscriptOnly":true,"message":{"content":"shown only"}}"#,
+            r#"{"message":{"content":[{"type":"tool_result","content":"ok"},{"type":"text","text":"ok"}]}}"#,
+            r#"{"message":{"content":"<local-command-stdout>cleared</local-command-stdout>"}}"#,
+            r#"{"message":{"content":"<bash-stdout>main</bash-stdout><bash-stderr></bash-stderr>"}}"#,
+            r#"{"message":{"content":[{"type":"text","text":"[Request interrupted by user for tool use]"}]}}"#,
+            r#"{"message":{"content":"   "}}"#,
+            r#"{"message":{}}"#,
+        ] {
+            assert_eq!(typed(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn counts_only_typed_turns() {
+        let path = session_file(
+            "typed",
+            "s1",
+            r#"{"type":"user","isMeta":true,"message":{"content":"Caveat"}}
+{"type":"user","message":{"content":"<command-name>/init</command-name>"}}
+{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Bash","input":{}}]}}
+{"type":"user","message":{"content":[{"type":"tool_result","content":"ok"}]}}
+{"type":"user","message":{"content":"thanks"}}
+"#,
+        );
+        let session = parse_jsonl_session(&path, None).unwrap();
+        assert_eq!(session.first_prompt.as_deref(), Some("/init"));
+        assert_eq!(session.message_count, Some(2));
+        assert_eq!(texts(&session), ["/init", "thanks"]);
+        cleanup(&path);
+    }
+}
@@ -1,10 +1,18 @@
-use super::{Session, SessionProvider};
+use super::Session;
+use super::claude::{
+    FileCheckpoint, MessageUsage, Role, TextKind, TokenUsage, TranscriptText, Turn, file_stamp,
+    tool_summary,
+};
+use super::provider::{Provider, ProviderStyle, agent_homes, home_of, with_home};
+use super::timestamp;
+use ratatui::style::Color;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::error::Error;
-use std::fs::{self, File};
+use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Deserialize)]
 struct SessionMeta {
@@ -31,45 +39,121 @@ struct HistoryEntry {
     text: String,
 }
 
-pub fn scan_sessions() -> Result<Vec<Session>, Box<dyn Error>> {
-    let codex_dir = dirs::home_dir()
-        .ok_or("Could not find home directory")?
-        .join(".codex")
-        .join("sessions");
+pub const NAME: &str = "codex";
 
-    if !codex_dir.exists() {
-        return Ok(Vec::new());
+/// Environment variable that moves Codex's home away from `~/.codex`
+const HOME_VAR: &str = "CODEX_HOME";
+const DEFAULT_HOME: &str = ".codex";
+
+/// Codex CLI, which keeps rollout files by date in a central store and
+/// first prompts in a shared history file
+pub struct CodexProvider {
+    homes: Vec<PathBuf>,
+    /// Loaded on the first parse, so scans where no file changed skip it
+    prompts: OnceLock<HashMap<String, String>>,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl CodexProvider {
+    /// `$CODEX_HOME` (or `~/.codex`) and the `extra` homes
+    pub fn new(extra: &[String]) -> Self {
+        CodexProvider {
+            homes: agent_homes(HOME_VAR, DEFAULT_HOME, extra),
+            prompts: OnceLock::new(),
+            warnings: Mutex::new(Vec::new()),
+        }
     }
+}
 
-    // Load history for first prompts
-    let first_prompts = load_history()?;
+impl Provider for CodexProvider {
+    fn name(&self) -> &str {
+        NAME
+    }
 
-    let mut sessions = Vec::new();
+    fn display_name(&self) -> &str {
+        "Codex"
+    }
 
-    // Glob ~/.codex/sessions/YYYY/MM/DD/*.jsonl
-    let pattern = codex_dir.join("*/*/*/*.jsonl");
-    let pattern_str = pattern.to_string_lossy();
+    fn session_files(&self, _projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut files = Vec::new();
+        for home in &self.homes {
+            files.extend(session_files(home)?);
+        }
+        Ok(files)
+    }
 
-    for entry in glob::glob(&pattern_str)? {
-        if let Ok(path) = entry {
-            match parse_session_file(&path, &first_prompts) {
-                Ok(session) => sessions.push(session),
-                Err(e) => {
-                    // Log but continue - handle missing fields gracefully
-                    eprintln!("Warning: failed to parse {:?}: {}", path, e);
+    /// Codex files are small enough to re-parse whole when they change
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        _previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        let prompts = self.prompts.get_or_init(|| {
+            let mut prompts = HashMap::new();
+            for home in &self.homes {
+                match load_history(home) {
+                    Ok(history) => prompts.extend(history),
+                    Err(e) => {
+                        if let Ok(mut warnings) = self.warnings.lock() {
+                            warnings.push(format!("failed to read Codex history: {}", e));
+                        }
+                    }
                 }
             }
+            prompts
+        });
+        let mut session = parse_session_file(path, prompts)?;
+        session.home = home_of(&self.homes, path).cloned();
+        Ok(vec![session])
+    }
+
+    fn read_turns(
+        &self,
+        path: &Path,
+        _uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>> {
+        read_turns(path, max_turns)
+    }
+
+    fn resume_command(&self, uuid: &str, _path: Option<&Path>, home: Option<&Path>) -> String {
+        with_home(
+            format!("codex resume {}", uuid),
+            HOME_VAR,
+            DEFAULT_HOME,
+            home,
+        )
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.warnings.lock().map(|w| w.clone()).unwrap_or_default()
+    }
+
+    fn style(&self) -> ProviderStyle {
+        ProviderStyle {
+            color: Color::Gray,
+            dim_color: Color::DarkGray,
         }
     }
+}
+
+/// List the session files in a Codex home (`<home>/sessions/YYYY/MM/DD/*.jsonl`)
+pub fn session_files(home: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let codex_dir = home.join("sessions");
+
+    if !codex_dir.exists() {
+        return Ok(Vec::new());
+    }
 
-    Ok(sessions)
+    let pattern = codex_dir.join("*/*/*/*.jsonl");
+    let pattern_str = pattern.to_string_lossy();
+
+    Ok(glob::glob(&pattern_str)?.flatten().collect())
 }
 
-fn load_history() -> Result<HashMap<String, String>, Box<dyn Error>> {
-    let history_path = dirs::home_dir()
-        .ok_or("Could not find home directory")?
-        .join(".codex")
-        .join("history.jsonl");
+/// Load the first prompt of each session from a Codex home's `history.jsonl`
+pub fn load_history(home: &Path) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    let history_path = home.join("history.jsonl");
 
     let mut prompts = HashMap::new();
 
@@ -81,38 +165,88 @@ fn load_history() -> Result<HashMap<String, String>, Box<dyn Error>> {
     let reader = BufReader::new(file);
 
     for line in reader.lines() {
-        if let Ok(line) = line {
-            if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
-                // Only keep first prompt per session
-                prompts.entry(entry.session_id).or_insert(entry.text);
-            }
+        if let Ok(line) = line
+            && let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line)
+        {
+            // Only keep first prompt per session
+            prompts.entry(entry.session_id).or_insert(entry.text);
         }
     }
 
     Ok(prompts)
 }
 
-fn parse_session_file(
-    path: &PathBuf,
+/// Parse a Codex session file: metadata from the leading `session_meta` line,
+/// transcript text from `response_item` messages, usage from `token_count`
+/// events and files from `apply_patch` calls. Codex files are always parsed
+/// whole; the checkpoint only lets unchanged files be skipped.
+pub fn parse_session_file(
+    path: &Path,
     first_prompts: &HashMap<String, String>,
 ) -> Result<Session, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    let (size, modified) = file_stamp(path)?;
 
-    // Read first line only
-    let first_line = reader.lines().next().ok_or("Empty session file")??;
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
 
-    let meta: SessionMeta = serde_json::from_str(&first_line)?;
+    // First line is the session metadata
+    let mut offset = reader.read_until(b'\n', &mut line)? as i64;
+    if offset == 0 {
+        return Err("Empty session file".into());
+    }
+    let meta: SessionMeta = serde_json::from_slice(&line)?;
 
     if meta.entry_type != "session_meta" {
         return Err("First line is not session_meta".into());
     }
 
-    // Get modified time from file metadata
-    let modified = fs::metadata(path)?
-        .modified()?
-        .duration_since(std::time::UNIX_EPOCH)?
-        .as_millis() as i64;
+    let mut transcript = Vec::new();
+    // Token counts are running totals; each increase is charged to the model
+    // of the latest `turn_context`
+    let mut usage = Vec::new();
+    let mut model = "unknown".to_string();
+    let mut counted = TokenUsage::default();
+    let cwd = Path::new(meta.payload.cwd.as_deref().unwrap_or_default());
+    let mut files = BTreeSet::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        offset += read as i64;
+
+        let Ok(item) = serde_json::from_slice::<serde_json::Value>(&line) else {
+            continue;
+        };
+        if let Some(text) = message_text(&item) {
+            transcript.push(text);
+        } else if let Some(patch) = patch_text(&item) {
+            // Patch paths are relative to the session's working directory
+            files.extend(patched_files(&patch).map(|p| cwd.join(p).to_string_lossy().to_string()));
+        } else if let Some(m) = turn_model(&item) {
+            model = m.to_string();
+        } else if let Some(total) = total_usage(&item) {
+            usage.push(MessageUsage {
+                // The event's offset, as the file is always parsed whole
+                id: (offset - read as i64).to_string(),
+                model: model.clone(),
+                timestamp: item
+                    .get("timestamp")
+                    .and_then(|t| t.as_str())
+                    .and_then(|t| timestamp::parse_ms(t, false))
+                    .unwrap_or(modified),
+                tokens: TokenUsage {
+                    input: (total.input - counted.input).max(0),
+                    output: (total.output - counted.output).max(0),
+                    cache_creation: 0,
+                    cache_read: (total.cache_read - counted.cache_read).max(0),
+                },
+            });
+            counted = total;
+        }
+    }
 
     let git_branch = meta.payload.git.and_then(|g| g.branch);
     let first_prompt = first_prompts.get(&meta.payload.id).cloned();
@@ -125,6 +259,203 @@ fn parse_session_file(
         first_prompt,
         modified,
         message_count: None, // Could count lines, but expensive
-        provider: SessionProvider::Codex,
+        provider: NAME.to_string(),
+        home: None,
+        checkpoint: Some(FileCheckpoint {
+            path: path.to_path_buf(),
+            size,
+            modified,
+            offset,
+        }),
+        transcript,
+        usage,
+        files,
+        boundary_ids: Vec::new(),
+        links: Vec::new(),
+        transcript_resumed: false,
+    })
+}
+
+/// User or assistant text of a `response_item` message line. Context Codex
+/// injects as user messages (`<environment_context>`, `<user_instructions>`)
+/// is skipped.
+fn message_text(item: &serde_json::Value) -> Option<TranscriptText> {
+    if item.get("type").and_then(|t| t.as_str()) != Some("response_item") {
+        return None;
+    }
+    let payload = item.get("payload")?;
+    if payload.get("type").and_then(|t| t.as_str()) != Some("message") {
+        return None;
+    }
+
+    let kind = match payload.get("role").and_then(|r| r.as_str())? {
+        "user" => TextKind::User,
+        "assistant" => TextKind::Assistant,
+        _ => return None,
+    };
+
+    // Codex blocks are `input_text` / `output_text` rather than `text`
+    let text = payload
+        .get("content")?
+        .as_array()?
+        .iter()
+        .filter(|b| {
+            matches!(
+                b.get("type").and_then(|t| t.as_str()),
+                Some("input_text" | "output_text")
+            )
+        })
+        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if text.trim().is_empty() || (kind == TextKind::User && text.trim_start().starts_with('<')) {
+        return None;
+    }
+    Some(TranscriptText { kind, text })
+}
+
+/// The patch of an `apply_patch` call, made either as its own tool or
+/// through the shell tool (`["apply_patch", "<patch>"]`)
+fn patch_text(item: &serde_json::Value) -> Option<String> {
+    if item.get("type").and_then(|t| t.as_str()) != Some("response_item") {
+        return None;
+    }
+    let payload = item.get("payload")?;
+    match payload.get("type").and_then(|t| t.as_str())? {
+        "custom_tool_call" if payload.get("name")?.as_str()? == "apply_patch" => {
+            Some(payload.get("input")?.as_str()?.to_string())
+        }
+        "function_call" => {
+            let arguments: serde_json::Value =
+                serde_json::from_str(payload.get("arguments")?.as_str()?).ok()?;
+            if payload.get("name")?.as_str()? == "apply_patch" {
+                return Some(arguments.get("input")?.as_str()?.to_string());
+            }
+            match arguments.get("command")?.as_array()?.as_slice() {
+                [cmd, patch] if cmd.as_str() == Some("apply_patch") => {
+                    Some(patch.as_str()?.to_string())
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Paths added, updated, deleted or moved to by a patch
+fn patched_files(patch: &str) -> impl Iterator<Item = &str> {
+    patch.lines().filter_map(|line| {
+        [
+            "*** Add File: ",
+            "*** Update File: ",
+            "*** Delete File: ",
+            "*** Move to: ",
+        ]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .map(str::trim)
+    })
+}
+
+/// Model of a `turn_context` line
+fn turn_model(item: &serde_json::Value) -> Option<&str> {
+    if item.get("type").and_then(|t| t.as_str()) != Some("turn_context") {
+        return None;
+    }
+    item.get("payload")?.get("model")?.as_str()
+}
+
+/// Session totals so far from a `token_count` event. Codex counts cached
+/// input as part of the input, so it is split off here.
+fn total_usage(item: &serde_json::Value) -> Option<TokenUsage> {
+    if item.get("type").and_then(|t| t.as_str()) != Some("event_msg") {
+        return None;
+    }
+    let payload = item.get("payload")?;
+    if payload.get("type").and_then(|t| t.as_str()) != Some("token_count") {
+        return None;
+    }
+    let total = payload.get("info")?.get("total_token_usage")?;
+    let count = |key: &str| total.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+    let cached = count("cached_input_tokens");
+    Some(TokenUsage {
+        input: count("input_tokens") - cached,
+        output: count("output_tokens"),
+        cache_creation: 0,
+        cache_read: cached,
+    })
+}
+
+/// Read the last `max_turns` user, assistant and tool-call turns of a session
+/// file for the preview
+pub fn read_turns(path: &Path, max_turns: usize) -> Result<Vec<Turn>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut turns = VecDeque::new();
+    let mut line = Vec::new();
+
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let item: Option<serde_json::Value> = serde_json::from_slice(&line).ok();
+        line.clear();
+        let Some(item) = item else {
+            continue;
+        };
+
+        let turn = match message_text(&item) {
+            Some(TranscriptText { kind, text }) => Some(Turn {
+                role: if kind == TextKind::User {
+                    Role::User
+                } else {
+                    Role::Assistant
+                },
+                text,
+            }),
+            None => tool_call(&item),
+        };
+        if let Some(turn) = turn {
+            if turns.len() == max_turns {
+                turns.pop_front();
+            }
+            turns.push_back(turn);
+        }
+    }
+
+    Ok(turns.into())
+}
+
+/// A `function_call`, `custom_tool_call` or `local_shell_call` item as a one-line turn
+fn tool_call(item: &serde_json::Value) -> Option<Turn> {
+    if item.get("type").and_then(|t| t.as_str()) != Some("response_item") {
+        return None;
+    }
+    let payload = item.get("payload")?;
+    let name = payload
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or("tool");
+
+    let text = match payload.get("type").and_then(|t| t.as_str())? {
+        // Arguments are a JSON object encoded as a string
+        "function_call" => {
+            let arguments = payload
+                .get("arguments")
+                .and_then(|a| a.as_str())
+                .and_then(|a| serde_json::from_str(a).ok())
+                .unwrap_or_default();
+            tool_summary(name, &arguments)
+        }
+        "custom_tool_call" => {
+            let input = payload.get("input").and_then(|i| i.as_str()).unwrap_or("");
+            format!("{}: {}", name, input.lines().next().unwrap_or_default())
+        }
+        "local_shell_call" => tool_summary(
+            "shell",
+            payload.get("action").unwrap_or(&serde_json::Value::Null),
+        ),
+        _ => return None,
+    };
+    Some(Turn {
+        role: Role::Tool,
+        text,
     })
 }
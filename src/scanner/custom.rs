@@ -0,0 +1,375 @@
+use super::Session;
+use super::claude::{FileCheckpoint, Role, TextKind, TranscriptText, Turn, file_stamp};
+use super::git;
+use super::provider::{Provider, ProviderStyle, shell_quote};
+use super::timestamp;
+use crate::config::{Config, ProviderConfig, SessionFormat};
+use ratatui::style::Color;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A provider declared in the config, reading one session per JSON or JSONL
+/// file through the JSON pointers it names
+pub struct CustomProvider {
+    config: ProviderConfig,
+    style: ProviderStyle,
+}
+
+impl CustomProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        let color = config
+            .color
+            .as_deref()
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(Color::Magenta);
+        let dim_color = match color {
+            Color::Rgb(r, g, b) => {
+                let dim = |c: u8| (c as u16 * 2 / 3) as u8;
+                Color::Rgb(dim(r), dim(g), dim(b))
+            }
+            _ => Color::DarkGray,
+        };
+        CustomProvider {
+            config,
+            style: ProviderStyle { color, dim_color },
+        }
+    }
+
+    /// The file's records: its lines, or the document and the elements of
+    /// its `messages` array. Lines that aren't JSON are skipped.
+    fn records(&self, path: &Path) -> Result<Vec<Value>, Box<dyn Error>> {
+        let content = fs::read_to_string(path)?;
+        match self.config.format {
+            SessionFormat::Jsonl => Ok(content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()),
+            SessionFormat::Json => {
+                let document: Value = serde_json::from_str(&content)?;
+                let messages = self
+                    .config
+                    .fields
+                    .messages
+                    .as_deref()
+                    .and_then(|pointer| document.pointer(pointer))
+                    .and_then(Value::as_array)
+                    .cloned()
+                    .unwrap_or_default();
+                Ok(std::iter::once(document).chain(messages).collect())
+            }
+        }
+    }
+
+    fn prompts(&self, records: &[Value]) -> Vec<String> {
+        let fields = &self.config.fields;
+        let Some(pointer) = fields.prompt.as_deref() else {
+            return Vec::new();
+        };
+        records
+            .iter()
+            .filter(|record| {
+                fields
+                    .role
+                    .as_deref()
+                    .is_none_or(|role| record.pointer(role).and_then(Value::as_str) == Some("user"))
+            })
+            .filter_map(|record| record.pointer(pointer))
+            .filter_map(value_text)
+            .collect()
+    }
+
+    /// Parse a session file through the configured pointers.
+    ///
+    /// Without an `id` the session id is derived from the file's path, and
+    /// without a `timestamp` the file's mtime is its last activity. A session
+    /// without a `branch` takes the one checked out in its cwd when first
+    /// scanned. Only prompts are known, so the preview shows just those.
+    fn parse_session_file(
+        &self,
+        path: &Path,
+        previous: Option<&Session>,
+    ) -> Result<Session, Box<dyn Error>> {
+        let (size, file_modified) = file_stamp(path)?;
+        let records = self.records(path)?;
+        let fields = &self.config.fields;
+        let field = |pointer: &Option<String>| {
+            let pointer = pointer.as_deref()?;
+            records
+                .iter()
+                .filter_map(|record| record.pointer(pointer))
+                .find_map(value_text)
+        };
+
+        let uuid = field(&fields.id).unwrap_or_else(|| {
+            let hash = Sha256::digest(path.to_string_lossy().as_bytes());
+            format!("{}-{}", self.config.name, &format!("{:x}", hash)[..16])
+        });
+        let project_path = field(&fields.cwd).unwrap_or_default();
+        let git_branch = field(&fields.branch)
+            .or_else(|| previous.and_then(|p| p.git_branch.clone()))
+            .or_else(|| {
+                (!project_path.is_empty())
+                    .then(|| git::head_branch(Path::new(&project_path)))
+                    .flatten()
+            });
+
+        let timestamps: Vec<&Value> = match fields.timestamp.as_deref() {
+            Some(pointer) => records.iter().filter_map(|r| r.pointer(pointer)).collect(),
+            None => Vec::new(),
+        };
+        let modified = timestamps_ms(&timestamps)
+            .into_iter()
+            .max()
+            .unwrap_or(file_modified);
+
+        let prompts = self.prompts(&records);
+        Ok(Session {
+            uuid,
+            project_path,
+            git_branch,
+            summary: None,
+            first_prompt: prompts.first().cloned(),
+            modified,
+            message_count: Some(prompts.len() as i64),
+            provider: self.config.name.clone(),
+            home: None,
+            checkpoint: Some(FileCheckpoint {
+                path: path.to_path_buf(),
+                size,
+                modified: file_modified,
+                offset: size,
+            }),
+            transcript: prompts
+                .into_iter()
+                .map(|text| TranscriptText {
+                    kind: TextKind::User,
+                    text,
+                })
+                .collect(),
+            usage: Vec::new(),
+            files: BTreeSet::new(),
+            boundary_ids: Vec::new(),
+            links: Vec::new(),
+            transcript_resumed: false,
+        })
+    }
+}
+
+impl Provider for CustomProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn display_name(&self) -> &str {
+        self.config
+            .display_name
+            .as_deref()
+            .unwrap_or(&self.config.name)
+    }
+
+    fn session_files(&self, _projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let pattern = Config::expand_path(&self.config.sessions);
+        Ok(glob::glob(&pattern.to_string_lossy())?.flatten().collect())
+    }
+
+    /// Files are re-read in full, as nothing is known about how they grow
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        Ok(vec![self.parse_session_file(path, previous.first())?])
+    }
+
+    fn read_turns(
+        &self,
+        path: &Path,
+        _uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>> {
+        let prompts = self.prompts(&self.records(path)?);
+        let skip = prompts.len().saturating_sub(max_turns);
+        Ok(prompts
+            .into_iter()
+            .skip(skip)
+            .map(|text| Turn {
+                role: Role::User,
+                text,
+            })
+            .collect())
+    }
+
+    fn resume_command(&self, uuid: &str, _path: Option<&Path>, _home: Option<&Path>) -> String {
+        self.config.resume.replace("{uuid}", &shell_quote(uuid))
+    }
+
+    fn style(&self) -> ProviderStyle {
+        self.style
+    }
+}
+
+/// Text of a field: a string, a number, or the strings (or `text` of the
+/// parts) of an array
+fn value_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.as_str().or_else(|| p.get("text")?.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Epoch ms of timestamps given as RFC 3339 strings or as seconds or ms
+/// since epoch. Ones that don't parse are left out.
+fn timestamps_ms(values: &[&Value]) -> Vec<i64> {
+    // Anything below this is in seconds (it's 1973 in ms)
+    const MIN_MS: f64 = 1e11;
+    let to_ms = |n: f64| if n < MIN_MS { n * 1000.0 } else { n } as i64;
+
+    values
+        .iter()
+        .filter_map(|value| match value {
+            Value::Number(n) => n.as_f64().map(to_ms),
+            Value::String(s) => match s.parse::<f64>() {
+                Ok(n) => Some(to_ms(n)),
+                Err(_) => timestamp::parse_ms(s, false),
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(config: &str) -> CustomProvider {
+        CustomProvider::new(toml::from_str(config).unwrap())
+    }
+
+    fn session_file(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ws-custom-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session.log");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn cleanup(path: &Path) {
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_jsonl_records_through_pointers() {
+        let provider = provider(
+            r#"
+            name = "jagent"
+            sessions = "/tmp/*.jsonl"
+            resume = "jagent --resume {uuid}"
+            fields = { id = "/session/id", cwd = "/cwd", branch = "/git/branch", prompt = "/text", role = "/role", timestamp = "/ts" }
+            "#,
+        );
+        let path = session_file(
+            "jsonl",
+            r#"{"session":{"id":"j-42"},"cwd":"/code/api","git":{"branch":"feat"},"ts":"2024-05-20T10:15:30Z"}
+{"role":"user","text":"fix the tests","ts":1716200131}
+not json
+{"role":"assistant","text":"done","ts":1716200132500}
+{"role":"user","text":[{"text":"and"},"the docs"],"ts":"1716200133"}
+"#,
+        );
+        let session = provider.parse_session_file(&path, None).unwrap();
+        assert_eq!(session.uuid, "j-42");
+        assert_eq!(session.provider, "jagent");
+        assert_eq!(session.project_path, "/code/api");
+        assert_eq!(session.git_branch.as_deref(), Some("feat"));
+        assert_eq!(session.first_prompt.as_deref(), Some("fix the tests"));
+        assert_eq!(session.message_count, Some(2));
+        assert_eq!(session.modified, 1_716_200_133_000);
+
+        let turns = provider.read_turns(&path, "j-42", 1).unwrap();
+        assert_eq!(turns.len(), 1);
+        assert_eq!(turns[0].text, "and\nthe docs");
+        cleanup(&path);
+    }
+
+    #[test]
+    fn reads_a_json_document_and_its_messages() {
+        let provider = provider(
+            r#"
+            name = "dagent"
+            sessions = "/tmp/*.json"
+            format = "json"
+            resume = "dagent open {uuid}"
+            fields = { cwd = "/meta/cwd", prompt = "/content", messages = "/messages" }
+            "#,
+        );
+        let path = session_file(
+            "json",
+            r#"{"meta":{"cwd":"/ws-test/nowhere"},"messages":[{"content":"hello"},{"content":7},{"content":""}]}"#,
+        );
+        let session = provider.parse_session_file(&path, None).unwrap();
+
+        // Named after the file when it has no id, last active at its mtime
+        let hash = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+        assert_eq!(session.uuid, format!("dagent-{}", &hash[..16]));
+        assert_eq!(session.modified, file_stamp(&path).unwrap().1);
+        assert_eq!(session.project_path, "/ws-test/nowhere");
+        assert_eq!(session.git_branch, None);
+        assert_eq!(session.message_count, Some(2));
+        assert_eq!(session.first_prompt.as_deref(), Some("hello"));
+
+        let mut previous = session.clone();
+        previous.git_branch = Some("main".to_string());
+        let session = provider.parse_session_file(&path, Some(&previous)).unwrap();
+        assert_eq!(session.git_branch.as_deref(), Some("main"));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn reads_timestamps_in_any_unit() {
+        let values: Vec<Value> = serde_json::from_str(
+            r#"[1716200132, 1716200132500, 1716200132.25, "1716200132", "2024-05-20T10:15:32Z", "soon", null]"#,
+        )
+        .unwrap();
+        let values: Vec<&Value> = values.iter().collect();
+        assert_eq!(
+            timestamps_ms(&values),
+            [
+                1_716_200_132_000,
+                1_716_200_132_500,
+                1_716_200_132_250,
+                1_716_200_132_000,
+                1_716_200_132_000
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_the_id_in_the_resume_command() {
+        let provider = provider(
+            r#"
+            name = "jagent"
+            sessions = "/tmp/*.jsonl"
+            resume = "jagent --resume {uuid}"
+            "#,
+        );
+        assert_eq!(
+            provider.resume_command("j-42", None, None),
+            "jagent --resume j-42"
+        );
+        assert_eq!(
+            provider.resume_command("it's; rm", None, None),
+            r"jagent --resume 'it'\''s; rm'"
+        );
+    }
+}
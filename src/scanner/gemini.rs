@@ -0,0 +1,472 @@
+use super::Session;
+use super::claude::{
+    FileCheckpoint, MessageUsage, Role, TextKind, TokenUsage, TranscriptText, Turn, file_stamp,
+    tool_summary,
+};
+use super::git;
+use super::provider::{Provider, ProviderStyle};
+use super::timestamp;
+use ratatui::style::Color;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const NAME: &str = "gemini";
+
+/// A chat file (`chats/session-<time>-<id>.json`), rewritten whole as the chat goes on
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Conversation {
+    session_id: String,
+    summary: Option<String>,
+    #[serde(default)]
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    id: Option<String>,
+    timestamp: Option<String>,
+    /// `user`, `gemini`, or `info`/`error`/`warning` notices
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    content: serde_json::Value,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+    tokens: Option<Tokens>,
+    model: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+/// Tokens of one reply. `input` includes the `cached` part.
+#[derive(Deserialize)]
+struct Tokens {
+    #[serde(default)]
+    input: i64,
+    #[serde(default)]
+    output: i64,
+    #[serde(default)]
+    cached: i64,
+    #[serde(default)]
+    thoughts: i64,
+}
+
+/// Gemini CLI, which keeps each chat as a JSON file under
+/// `~/.gemini/tmp/<project hash>/chats/`, the hash being the SHA-256 of the
+/// directory it ran in. `/chat save` checkpoints in the same directory are
+/// copies of a chat and aren't listed separately.
+pub struct GeminiProvider {
+    /// Project hash to directory, built by `session_files` from the known projects
+    projects: OnceLock<HashMap<String, PathBuf>>,
+}
+
+impl GeminiProvider {
+    pub fn new() -> Self {
+        GeminiProvider {
+            projects: OnceLock::new(),
+        }
+    }
+}
+
+impl Provider for GeminiProvider {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn display_name(&self) -> &str {
+        "Gemini"
+    }
+
+    fn session_files(&self, projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let _ = self.projects.set(
+            projects
+                .iter()
+                .map(|p| (project_hash(p), p.clone()))
+                .collect(),
+        );
+        session_files()
+    }
+
+    /// Chat files are rewritten whole, so they are always re-read in full
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>> {
+        let projects = self.projects.get_or_init(HashMap::new);
+        Ok(vec![parse_session_file(path, projects, previous.first())?])
+    }
+
+    fn read_turns(
+        &self,
+        path: &Path,
+        _uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>> {
+        read_turns(path, max_turns)
+    }
+
+    fn resume_command(&self, uuid: &str, _path: Option<&Path>, _home: Option<&Path>) -> String {
+        format!("gemini --resume {}", uuid)
+    }
+
+    /// Gemini only resumes chats stored under the current directory's hash
+    fn migrate_session(
+        &self,
+        uuid: &str,
+        _home: Option<&Path>,
+        source: &Path,
+        target: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        migrate_session(uuid, source, target)
+    }
+
+    fn style(&self) -> ProviderStyle {
+        ProviderStyle {
+            color: Color::Rgb(66, 133, 244),    // Blue
+            dim_color: Color::Rgb(45, 95, 180), // Darker blue
+        }
+    }
+}
+
+/// Name of the directory under `~/.gemini/tmp` holding a project's chats
+pub fn project_hash(project: &Path) -> String {
+    format!("{:x}", Sha256::digest(project.to_string_lossy().as_bytes()))
+}
+
+fn tmp_dir() -> Result<PathBuf, Box<dyn Error>> {
+    Ok(dirs::home_dir()
+        .ok_or("Could not find home directory")?
+        .join(".gemini")
+        .join("tmp"))
+}
+
+/// List all Gemini chat files (`~/.gemini/tmp/*/chats/session-*.json`)
+pub fn session_files() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let tmp_dir = tmp_dir()?;
+    if !tmp_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let pattern = tmp_dir.join("*").join("chats").join("session-*.json");
+    Ok(glob::glob(&pattern.to_string_lossy())?.flatten().collect())
+}
+
+/// Parse a chat file into a Session.
+///
+/// The cwd is the project whose hash names the chat's directory, or empty if
+/// no known project hashes to it. Gemini doesn't record the branch, so a
+/// session takes the one checked out in its worktree when first scanned.
+/// `message_count` counts user messages; `usage` and `files` come from the
+/// replies' token counts and `write_file`/`replace` tool calls.
+pub fn parse_session_file(
+    path: &Path,
+    projects: &HashMap<String, PathBuf>,
+    previous: Option<&Session>,
+) -> Result<Session, Box<dyn Error>> {
+    let (size, modified) = file_stamp(path)?;
+    let conversation: Conversation = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    // `<hash>/chats/<file>`
+    let project = path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .and_then(|hash| projects.get(hash.to_str()?));
+    let git_branch = previous
+        .and_then(|p| p.git_branch.clone())
+        .or_else(|| project.and_then(|p| git::head_branch(p)));
+
+    let mut first_prompt = None;
+    let mut message_count = 0;
+    let mut transcript = Vec::new();
+    let mut usage = Vec::new();
+    let mut files = BTreeSet::new();
+    for (i, message) in conversation.messages.iter().enumerate() {
+        let text = content_text(&message.content);
+        match message.kind.as_str() {
+            "user" => {
+                let Some(text) = text else {
+                    continue;
+                };
+                message_count += 1;
+                if first_prompt.is_none() {
+                    first_prompt = Some(text.clone());
+                }
+                transcript.push(TranscriptText {
+                    kind: TextKind::User,
+                    text,
+                });
+            }
+            "gemini" => {
+                if let Some(text) = text {
+                    transcript.push(TranscriptText {
+                        kind: TextKind::Assistant,
+                        text,
+                    });
+                }
+                for call in &message.tool_calls {
+                    if matches!(call.name.as_str(), "write_file" | "replace")
+                        && let Some(file) = call.args.get("file_path").and_then(|f| f.as_str())
+                    {
+                        files.insert(file.to_string());
+                    }
+                }
+                if let (Some(model), Some(tokens)) = (&message.model, &message.tokens) {
+                    usage.push(MessageUsage {
+                        id: message.id.clone().unwrap_or_else(|| i.to_string()),
+                        model: model.clone(),
+                        timestamp: message
+                            .timestamp
+                            .as_deref()
+                            .and_then(|t| timestamp::parse_ms(t, false))
+                            .unwrap_or(modified),
+                        tokens: TokenUsage {
+                            input: (tokens.input - tokens.cached).max(0),
+                            // Thinking is billed as output
+                            output: tokens.output + tokens.thoughts,
+                            cache_creation: 0,
+                            cache_read: tokens.cached,
+                        },
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Session {
+        uuid: conversation.session_id,
+        project_path: project
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        git_branch,
+        summary: conversation.summary,
+        first_prompt,
+        modified,
+        message_count: Some(message_count),
+        provider: NAME.to_string(),
+        home: None,
+        checkpoint: Some(FileCheckpoint {
+            path: path.to_path_buf(),
+            size,
+            modified,
+            offset: size,
+        }),
+        transcript,
+        usage,
+        files,
+        boundary_ids: Vec::new(),
+        links: Vec::new(),
+        transcript_resumed: false,
+    })
+}
+
+/// Text of a message: a plain string or the `text` of each part
+fn content_text(content: &serde_json::Value) -> Option<String> {
+    let text = match content {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => return None,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// Read the last `max_turns` user, assistant and tool-call turns of a chat
+/// file for the preview
+pub fn read_turns(path: &Path, max_turns: usize) -> Result<Vec<Turn>, Box<dyn Error>> {
+    let conversation: Conversation = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+    let mut turns = VecDeque::new();
+    for message in conversation.messages {
+        let role = match message.kind.as_str() {
+            "user" => Role::User,
+            "gemini" => Role::Assistant,
+            _ => continue,
+        };
+        let text = content_text(&message.content).map(|text| Turn { role, text });
+        let calls = message.tool_calls.iter().map(|call| Turn {
+            role: Role::Tool,
+            text: tool_summary(&call.name, &call.args),
+        });
+        for turn in text.into_iter().chain(calls) {
+            if turns.len() == max_turns {
+                turns.pop_front();
+            }
+            turns.push_back(turn);
+        }
+    }
+
+    Ok(turns.into())
+}
+
+/// Copy a chat into the directory of `target_project`, so `gemini --resume`
+/// finds it there
+fn migrate_session(
+    session_uuid: &str,
+    source_project: &Path,
+    target_project: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tmp_dir()?;
+    let target_hash = project_hash(target_project);
+    let source_chats = tmp_dir.join(project_hash(source_project)).join("chats");
+    let target_chats = tmp_dir.join(&target_hash).join("chats");
+
+    let pattern = source_chats.join("session-*.json");
+    for source in glob::glob(&pattern.to_string_lossy())?.flatten() {
+        let Ok(mut chat) = serde_json::from_slice::<serde_json::Value>(&fs::read(&source)?) else {
+            continue;
+        };
+        if chat.get("sessionId").and_then(|id| id.as_str()) != Some(session_uuid) {
+            continue;
+        }
+
+        let target = target_chats.join(source.file_name().ok_or("Invalid chat file name")?);
+        if target.exists() {
+            // Already migrated
+            return Ok(());
+        }
+        chat["projectHash"] = target_hash.into();
+        fs::create_dir_all(&target_chats)?;
+        fs::write(&target, serde_json::to_vec_pretty(&chat)?)?;
+        return Ok(());
+    }
+
+    Err(format!(
+        "Gemini chat {} not found in {:?}",
+        session_uuid, source_chats
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAT: &str = r#"{
+      "sessionId": "5f0c2c1e-chat",
+      "projectHash": "ignored",
+      "summary": "Add a Gemini provider",
+      "messages": [
+        {"id": "m1", "type": "info", "content": "Logged in"},
+        {"id": "m2", "type": "user", "timestamp": "2024-05-20T10:15:30Z",
+         "content": [{"text": "add a provider"}]},
+        {"id": "m3", "type": "gemini", "timestamp": "2024-05-20T10:15:32Z",
+         "content": "Writing it now", "model": "gemini-2.5-pro",
+         "tokens": {"input": 120, "output": 30, "cached": 100, "thoughts": 12, "total": 162},
+         "toolCalls": [
+           {"name": "write_file", "args": {"file_path": "/code/api/gemini.rs"}},
+           {"name": "read_file", "args": {"file_path": "/code/api/claude.rs"}},
+           {"name": "replace", "args": {"file_path": "/code/api/mod.rs"}}
+         ]},
+        {"id": "m4", "type": "user", "content": "thanks"},
+        {"type": "gemini", "content": "", "model": "gemini-2.5-flash", "tokens": {"output": 2}}
+      ]
+    }"#;
+
+    /// A project with `main` checked out and `CHAT` saved under its hash,
+    /// returning the project and the chat file
+    fn chat_file(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("ws-gemini-{}-{}", std::process::id(), name));
+        let project = dir.join("api");
+        fs::create_dir_all(project.join(".git")).unwrap();
+        fs::write(project.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        let chats = dir.join(project_hash(&project)).join("chats");
+        fs::create_dir_all(&chats).unwrap();
+        let path = chats.join("session-2024-05-20T10-15-5f0c2c1e.json");
+        fs::write(&path, CHAT).unwrap();
+        (project, path)
+    }
+
+    #[test]
+    fn parses_a_chat() {
+        let (project, path) = chat_file("parse");
+        let projects = HashMap::from([(project_hash(&project), project.clone())]);
+        let session = parse_session_file(&path, &projects, None).unwrap();
+
+        assert_eq!(session.uuid, "5f0c2c1e-chat");
+        assert_eq!(session.project_path, project.to_string_lossy());
+        assert_eq!(session.git_branch.as_deref(), Some("main"));
+        assert_eq!(session.summary.as_deref(), Some("Add a Gemini provider"));
+        assert_eq!(session.first_prompt.as_deref(), Some("add a provider"));
+        assert_eq!(session.message_count, Some(2));
+        let texts: Vec<&str> = session.transcript.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, ["add a provider", "Writing it now", "thanks"]);
+        assert_eq!(
+            session.files.iter().collect::<Vec<_>>(),
+            ["/code/api/gemini.rs", "/code/api/mod.rs"]
+        );
+        assert_eq!(
+            session.usage[0],
+            MessageUsage {
+                id: "m3".to_string(),
+                model: "gemini-2.5-pro".to_string(),
+                timestamp: 1_716_200_132_000,
+                tokens: TokenUsage {
+                    input: 20,
+                    output: 42,
+                    cache_creation: 0,
+                    cache_read: 100,
+                },
+            }
+        );
+        // Without an id or timestamp: its position and the file's mtime
+        assert_eq!(session.usage[1].id, "4");
+        assert_eq!(session.usage[1].timestamp, session.modified);
+        let checkpoint = session.checkpoint.unwrap();
+        assert_eq!(checkpoint.offset, checkpoint.size);
+
+        fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn keeps_the_branch_a_chat_was_first_seen_on() {
+        let (project, path) = chat_file("branch");
+        let projects = HashMap::from([(project_hash(&project), project.clone())]);
+        let mut previous = parse_session_file(&path, &projects, None).unwrap();
+        previous.git_branch = Some("feature".to_string());
+        let session = parse_session_file(&path, &projects, Some(&previous)).unwrap();
+        assert_eq!(session.git_branch.as_deref(), Some("feature"));
+
+        // A chat of an unknown project has no cwd
+        let session = parse_session_file(&path, &HashMap::new(), None).unwrap();
+        assert_eq!(session.project_path, "");
+        assert_eq!(session.git_branch, None);
+
+        fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_the_last_turns() {
+        let (project, path) = chat_file("turns");
+        let turns = read_turns(&path, 3).unwrap();
+        let turns: Vec<(Role, &str)> = turns.iter().map(|t| (t.role, t.text.as_str())).collect();
+        assert_eq!(
+            turns,
+            [
+                (Role::Tool, "read_file: /code/api/claude.rs"),
+                (Role::Tool, "replace: /code/api/mod.rs"),
+                (Role::User, "thanks"),
+            ]
+        );
+
+        fs::remove_dir_all(project.parent().unwrap()).unwrap();
+    }
+}
@@ -1,13 +1,16 @@
-use crate::config::Config;
+use crate::config::{Config, ScanDir};
+use std::collections::{BTreeMap, HashSet};
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone)]
 pub struct Repo {
     pub path: PathBuf,
     pub name: String,
+    pub git_common_dir: Option<PathBuf>, // shared by the repo and all its worktrees
     pub worktrees: Vec<Worktree>,
 }
 
@@ -17,88 +20,418 @@ pub struct Worktree {
     pub branch: Option<String>,
 }
 
-impl Worktree {
-    /// Check if worktree has uncommitted changes (expensive, call sparingly)
-    pub fn is_dirty(&self) -> bool {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                self.path.to_str().unwrap_or(""),
-                "status",
-                "--porcelain",
-            ])
-            .output();
-
-        match output {
-            Ok(out) => !out.stdout.is_empty(),
-            Err(_) => false,
+/// Subject of the commit ws creates when pausing work in a dirty worktree
+pub const WIP_COMMIT_SUBJECT: &str = "WIP: paused work";
+
+/// Snapshot of a worktree's git state, as shown next to its dot in the tree
+#[derive(Debug, Clone, Default)]
+pub struct WorktreeStatus {
+    pub head_sha: Option<String>,
+    pub head_subject: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: u32,
+    pub behind: u32,
+    pub staged: u32,
+    pub unstaged: u32,
+    pub untracked: u32,
+    pub conflicted: u32,
+    pub stash_count: u32,
+    pub operation: Option<GitOperation>,
+}
+
+/// A multi-step git operation left in progress in a worktree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitOperation {
+    Rebase,
+    Merge,
+    CherryPick,
+    Revert,
+}
+
+impl GitOperation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitOperation::Rebase => "rebase",
+            GitOperation::Merge => "merge",
+            GitOperation::CherryPick => "cherry-pick",
+            GitOperation::Revert => "revert",
         }
     }
+}
+
+impl WorktreeStatus {
+    /// Uncommitted changes of any kind, including untracked files and conflicts
+    pub fn is_dirty(&self) -> bool {
+        self.staged + self.unstaged + self.untracked + self.conflicted > 0
+    }
 
-    /// Check if the most recent commit is a WIP commit
+    pub fn is_conflicted(&self) -> bool {
+        self.conflicted > 0
+    }
+
+    /// Whether HEAD is the WIP commit ws made when this worktree was last paused
     pub fn has_wip_commit(&self) -> bool {
-        let output = Command::new("git")
-            .args([
-                "-C",
-                self.path.to_str().unwrap_or(""),
-                "log",
-                "-1",
-                "--format=%s",
-            ])
-            .output();
-
-        match output {
-            Ok(out) => {
-                let msg = String::from_utf8_lossy(&out.stdout);
-                msg.trim() == "WIP: paused work"
+        self.head_subject.as_deref() == Some(WIP_COMMIT_SUBJECT)
+    }
+}
+
+impl Worktree {
+    /// Collect the full git status of this worktree (expensive, call sparingly)
+    pub fn status(&self) -> WorktreeStatus {
+        let mut status = WorktreeStatus::default();
+        let path = self.path.to_str().unwrap_or("");
+
+        if let Some(out) = git_output(&["-C", path, "status", "--porcelain=v2", "--branch"]) {
+            parse_porcelain_v2(&out, &mut status);
+        }
+
+        if let Some(sha) = &status.head_sha {
+            status.head_subject = head_subject(path, sha);
+        }
+
+        let git_dir = worktree_git_dir(&self.path);
+        if let Some(dir) = &git_dir {
+            status.stash_count = stash_count(dir, head_branch(&self.path).as_deref());
+        }
+        status.operation = git_dir.and_then(|dir| operation_in_progress(&dir));
+
+        status
+    }
+}
+
+/// Subjects of commits already looked up, by sha. HEAD rarely moves between
+/// status refreshes, so this saves running `git log` on most of them.
+static HEAD_SUBJECTS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+fn head_subject(path: &str, sha: &str) -> Option<String> {
+    if let Some(subject) = HEAD_SUBJECTS.lock().ok()?.get(sha) {
+        return Some(subject.clone());
+    }
+    let subject = git_output(&["-C", path, "log", "-1", "--format=%s", sha])?
+        .trim()
+        .to_string();
+    HEAD_SUBJECTS
+        .lock()
+        .ok()?
+        .insert(sha.to_string(), subject.clone());
+    Some(subject)
+}
+
+/// Stashes made on `branch` (or on a detached HEAD), read from the repo's
+/// stash reflog. Stashes are shared by all worktrees of a repo; their
+/// messages (`WIP on <branch>: ...`, `On <branch>: ...`) tell where each was made.
+fn stash_count(git_dir: &Path, branch: Option<&str>) -> u32 {
+    // Linked worktrees name the shared git dir in `commondir`
+    let common_dir = std::fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf());
+    let Ok(reflog) = std::fs::read_to_string(common_dir.join("logs/refs/stash")) else {
+        return 0;
+    };
+
+    let branch = branch.unwrap_or("(no branch)");
+    let prefixes = [format!("WIP on {}: ", branch), format!("On {}: ", branch)];
+    reflog
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(_, message)| prefixes.iter().any(|p| message.starts_with(p.as_str())))
+        .count() as u32
+}
+
+/// Run git and return its stdout, or None if it couldn't run or failed
+fn git_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Fill in branch and file counts from `git status --porcelain=v2 --branch`
+fn parse_porcelain_v2(output: &str, status: &mut WorktreeStatus) {
+    for line in output.lines() {
+        if let Some(oid) = line.strip_prefix("# branch.oid ") {
+            if oid != "(initial)" {
+                status.head_sha = Some(oid.to_string());
+            }
+        } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+            status.upstream = Some(upstream.to_string());
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            // Format: "+<ahead> -<behind>"
+            let mut parts = ab.split_whitespace();
+            status.ahead = parts
+                .next()
+                .and_then(|a| a.trim_start_matches('+').parse().ok())
+                .unwrap_or(0);
+            status.behind = parts
+                .next()
+                .and_then(|b| b.trim_start_matches('-').parse().ok())
+                .unwrap_or(0);
+        } else if line.starts_with("1 ") || line.starts_with("2 ") {
+            // Ordinary or renamed entry: "<n> XY ...", '.' means unchanged
+            let mut xy = line[2..].chars();
+            if xy.next().is_some_and(|x| x != '.') {
+                status.staged += 1;
+            }
+            if xy.next().is_some_and(|y| y != '.') {
+                status.unstaged += 1;
             }
-            Err(_) => false,
+        } else if line.starts_with("u ") {
+            status.conflicted += 1;
+        } else if line.starts_with("? ") {
+            status.untracked += 1;
+        }
+    }
+}
+
+/// Resolve the git dir holding a worktree's HEAD and in-progress state files.
+/// Linked worktrees have a `.git` file pointing at `<common>/worktrees/<name>`.
+fn worktree_git_dir(worktree_path: &Path) -> Option<PathBuf> {
+    let dot_git = worktree_path.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+
+    let contents = std::fs::read_to_string(&dot_git).ok()?;
+    let git_dir = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(worktree_path.join(git_dir))
+}
+
+/// Branch checked out in the worktree containing `path`, read from its HEAD
+/// file without running git. `None` when detached or outside a worktree.
+pub fn head_branch(path: &Path) -> Option<String> {
+    let git_dir = path.ancestors().find_map(worktree_git_dir)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(|b| b.to_string())
+}
+
+/// Paths of a repo found by `find_repos` and of its linked worktrees, read
+/// from `<git dir>/worktrees/*/gitdir` so discovery needn't run git
+pub fn worktree_paths(repo_path: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![repo_path.to_path_buf()];
+    let git_dir = worktree_git_dir(repo_path).unwrap_or_else(|| repo_path.to_path_buf());
+    let Ok(entries) = std::fs::read_dir(git_dir.join("worktrees")) else {
+        return paths;
+    };
+    for entry in entries.flatten() {
+        // `gitdir` holds the path of the worktree's `.git` file
+        if let Ok(gitdir) = std::fs::read_to_string(entry.path().join("gitdir"))
+            && let Some(worktree) = Path::new(gitdir.trim()).parent()
+        {
+            paths.push(worktree.to_path_buf());
         }
     }
+    paths
 }
 
-pub fn scan_repos(scan_dirs: &[String]) -> Result<Vec<Repo>, Box<dyn Error>> {
+fn operation_in_progress(git_dir: &Path) -> Option<GitOperation> {
+    if git_dir.join("rebase-merge").exists() || git_dir.join("rebase-apply").exists() {
+        Some(GitOperation::Rebase)
+    } else if git_dir.join("MERGE_HEAD").exists() {
+        Some(GitOperation::Merge)
+    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+        Some(GitOperation::CherryPick)
+    } else if git_dir.join("REVERT_HEAD").exists() {
+        Some(GitOperation::Revert)
+    } else {
+        None
+    }
+}
+
+/// Walk the scan dirs and return the root path of every distinct repo found.
+/// A linked worktree whose repo is outside every scan dir brings that repo
+/// in too. Directories that can't be read are skipped with a warning.
+///
+/// Only discovery happens here; listing worktrees is left to `scan_single_repo`
+/// so callers can run it in parallel.
+pub fn find_repos(
+    scan_dirs: &[ScanDir],
+    warnings: &mut Vec<String>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
     let mut repos = Vec::new();
+    // Canonical common git dirs of repos already found, so overlapping scan
+    // roots or worktrees living under another root don't produce duplicates
+    let mut seen_git_dirs: HashSet<PathBuf> = HashSet::new();
+    // Common git dirs of linked worktrees, resolved once the walk is done
+    let mut linked_git_dirs: Vec<PathBuf> = Vec::new();
 
     for dir in scan_dirs {
-        let expanded = Config::expand_path(dir);
+        let expanded = Config::expand_path(dir.path());
         if !expanded.exists() {
             continue;
         }
 
-        // Walk one level deep to find git repos
-        for entry in WalkDir::new(&expanded).min_depth(1).max_depth(1) {
-            let entry = entry?;
-            if entry.file_type().is_dir() {
-                let git_dir = entry.path().join(".git");
-                // Only count as repo if .git is a directory (real repo), not a file (linked worktree)
-                if git_dir.is_dir() {
-                    if let Ok(repo) = scan_single_repo(entry.path()) {
-                        repos.push(repo);
-                    }
+        let mut walker = WalkDir::new(&expanded)
+            .min_depth(1)
+            .max_depth(dir.max_depth())
+            .into_iter();
+
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warnings.push(format!("skipped unreadable directory: {}", e));
+                    continue;
+                }
+            };
+            if !entry.file_type().is_dir() {
+                continue;
+            }
+
+            let path = entry.path();
+            let Some(kind) = detect_repo(path) else {
+                continue;
+            };
+
+            // Never descend into a repo or worktree, its subdirectories are not separate repos
+            walker.skip_current_dir();
+
+            let git_dir = match kind {
+                RepoKind::LinkedWorktree => {
+                    linked_git_dirs.extend(git_common_dir(path));
+                    continue;
                 }
+                RepoKind::Normal => path.join(".git"),
+                RepoKind::Bare => path.to_path_buf(),
+                RepoKind::BareContainer(git_dir) => git_dir,
+            };
+            let git_dir = git_dir.canonicalize().unwrap_or(git_dir);
+            if seen_git_dirs.insert(git_dir) {
+                repos.push(path.to_path_buf());
             }
         }
     }
 
+    // The repo of a worktree is usually found by the walk too; scan the rest
+    // from their common dir: the clone holding `.git`, or the bare repo itself
+    for git_dir in linked_git_dirs {
+        if !seen_git_dirs.insert(git_dir.clone()) {
+            continue;
+        }
+        let root = match git_dir.file_name() {
+            Some(name) if name == ".git" => git_dir.parent().map(Path::to_path_buf),
+            _ => Some(git_dir),
+        };
+        repos.extend(root);
+    }
+
     Ok(repos)
 }
 
-fn scan_single_repo(path: &Path) -> Result<Repo, Box<dyn Error>> {
-    let name = path
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| "unknown".to_string());
+/// How a directory found during the walk relates to git
+enum RepoKind {
+    /// Regular clone with a `.git` directory
+    Normal,
+    /// The directory itself is a bare repository (e.g. `project/.bare` or `project.git`)
+    Bare,
+    /// `.git` file pointing at a bare repository nested inside the directory,
+    /// as in the `project/.git` -> `project/.bare` layout
+    BareContainer(PathBuf),
+    /// `.git` file pointing at some other repo; picked up via that repo's worktree list
+    LinkedWorktree,
+}
+
+fn detect_repo(path: &Path) -> Option<RepoKind> {
+    let dot_git = path.join(".git");
+    if dot_git.is_dir() {
+        return Some(RepoKind::Normal);
+    }
+
+    if dot_git.is_file() {
+        // The common dir is canonical, so compare it against the canonical path
+        // or a symlinked scan root would never contain it
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(common_dir) = git_common_dir(path)
+            && common_dir.starts_with(&canonical)
+            && is_bare_repository(&common_dir)
+        {
+            return Some(RepoKind::BareContainer(common_dir));
+        }
+        return Some(RepoKind::LinkedWorktree);
+    }
+
+    // Cheap layout check before asking git, so we don't spawn a process per directory
+    let looks_bare =
+        path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir();
+    if looks_bare && is_bare_repository(path) {
+        return Some(RepoKind::Bare);
+    }
+
+    None
+}
+
+fn is_bare_repository(git_dir: &Path) -> bool {
+    let output = Command::new("git")
+        .args([
+            "--git-dir",
+            git_dir.to_str().unwrap_or(""),
+            "rev-parse",
+            "--is-bare-repository",
+        ])
+        .output();
+
+    match output {
+        Ok(out) => out.status.success() && String::from_utf8_lossy(&out.stdout).trim() == "true",
+        Err(_) => false,
+    }
+}
+
+/// Canonical git dir shared by all worktrees of the repo containing `path`
+pub fn git_common_dir(path: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args([
+            "-C",
+            path.to_str().unwrap_or(""),
+            "rev-parse",
+            "--path-format=absolute",
+            "--git-common-dir",
+        ])
+        .output()
+        .ok()?;
 
-    let worktrees = parse_worktree_list(path)?;
+    if !output.status.success() {
+        return None;
+    }
+    let common_dir = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let common_dir = PathBuf::from(common_dir);
+    Some(common_dir.canonicalize().unwrap_or(common_dir))
+}
+
+pub fn scan_single_repo(path: &Path) -> Result<Repo, Box<dyn Error>> {
+    // Canonical paths so session cwds can be matched against them component-wise
+    let path = path.canonicalize()?;
+    let worktrees = parse_worktree_list(&path)?;
 
     Ok(Repo {
-        path: path.to_path_buf(),
-        name,
+        name: repo_name(&path),
+        git_common_dir: git_common_dir(&path),
+        path,
         worktrees,
     })
 }
 
+/// Display name for a repo: bare dirs named `.bare`/`.git` take their parent's
+/// name, and a `.git` suffix (`project.git`) is dropped
+fn repo_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    match file_name.as_str() {
+        ".bare" | ".git" => path
+            .parent()
+            .map(repo_name)
+            .unwrap_or_else(|| "unknown".to_string()),
+        _ => file_name
+            .strip_suffix(".git")
+            .map(|s| s.to_string())
+            .unwrap_or(file_name),
+    }
+}
+
 fn parse_worktree_list(repo_path: &Path) -> Result<Vec<Worktree>, Box<dyn Error>> {
     let output = Command::new("git")
         .args([
@@ -109,25 +442,33 @@ fn parse_worktree_list(repo_path: &Path) -> Result<Vec<Worktree>, Box<dyn Error>
             "--porcelain",
         ])
         .output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+    }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
     let mut worktrees = Vec::new();
     let mut current_path: Option<PathBuf> = None;
     let mut current_branch: Option<String> = None;
+    let mut current_is_bare = false;
 
     for line in stdout.lines() {
-        if line.starts_with("worktree ") {
-            // Save previous worktree if any
-            if let Some(path) = current_path.take() {
+        if let Some(path) = line.strip_prefix("worktree ") {
+            // Save previous worktree if any (the bare repo itself has no checkout)
+            if let Some(prev) = current_path.take()
+                && !current_is_bare
+            {
                 worktrees.push(Worktree {
-                    path,
+                    path: prev,
                     branch: current_branch.take(),
                 });
             }
-            current_path = Some(PathBuf::from(&line[9..]));
-        } else if line.starts_with("branch ") {
+            let path = PathBuf::from(path);
+            current_path = Some(path.canonicalize().unwrap_or(path));
+            current_branch = None;
+            current_is_bare = false;
+        } else if let Some(full_ref) = line.strip_prefix("branch ") {
             // Extract branch name from refs/heads/...
-            let full_ref = &line[7..];
             let branch = full_ref
                 .strip_prefix("refs/heads/")
                 .unwrap_or(full_ref)
@@ -135,11 +476,15 @@ fn parse_worktree_list(repo_path: &Path) -> Result<Vec<Worktree>, Box<dyn Error>
             current_branch = Some(branch);
         } else if line.starts_with("detached") {
             current_branch = None;
+        } else if line == "bare" {
+            current_is_bare = true;
         }
     }
 
     // Save last worktree
-    if let Some(path) = current_path {
+    if let Some(path) = current_path
+        && !current_is_bare
+    {
         worktrees.push(Worktree {
             path,
             branch: current_branch,
@@ -148,3 +493,81 @@ fn parse_worktree_list(repo_path: &Path) -> Result<Vec<Worktree>, Box<dyn Error>
 
     Ok(worktrees)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(output: &str) -> WorktreeStatus {
+        let mut status = WorktreeStatus::default();
+        parse_porcelain_v2(output, &mut status);
+        status
+    }
+
+    #[test]
+    fn parses_branch_headers() {
+        let status = parse(
+            "# branch.oid 1f2e3d4c\n\
+             # branch.head feature/x\n\
+             # branch.upstream origin/feature/x\n\
+             # branch.ab +3 -1\n",
+        );
+        assert_eq!(status.head_sha.as_deref(), Some("1f2e3d4c"));
+        assert_eq!(status.upstream.as_deref(), Some("origin/feature/x"));
+        assert_eq!((status.ahead, status.behind), (3, 1));
+        assert!(!status.is_dirty());
+    }
+
+    #[test]
+    fn initial_commit_has_no_head() {
+        let status = parse("# branch.oid (initial)\n# branch.head main\n");
+        assert_eq!(status.head_sha, None);
+        assert_eq!(status.upstream, None);
+    }
+
+    #[test]
+    fn counts_entries() {
+        let status = parse(
+            "# branch.oid 1f2e3d4c\n\
+             1 M. N... 100644 100644 100644 aaa bbb staged.rs\n\
+             1 .M N... 100644 100644 100644 aaa aaa unstaged.rs\n\
+             1 MM N... 100644 100644 100644 aaa bbb both.rs\n\
+             2 R. N... 100644 100644 100644 aaa aaa R100 new.rs\told.rs\n\
+             u UU N... 100644 100644 100644 100644 aaa bbb ccc conflict.rs\n\
+             ? untracked.rs\n\
+             ? other.rs\n\
+             ! ignored.rs\n",
+        );
+        assert_eq!(status.staged, 3);
+        assert_eq!(status.unstaged, 2);
+        assert_eq!(status.conflicted, 1);
+        assert_eq!(status.untracked, 2);
+        assert!(status.is_dirty());
+        assert!(status.is_conflicted());
+    }
+
+    #[test]
+    fn counts_stashes_of_the_branch() {
+        let dir = std::env::temp_dir().join(format!("ws-stash-test-{}", std::process::id()));
+        let common = dir.join("repo/.git");
+        let linked = common.join("worktrees/feat");
+        std::fs::create_dir_all(common.join("logs/refs")).unwrap();
+        std::fs::create_dir_all(&linked).unwrap();
+        std::fs::write(linked.join("commondir"), "../..\n").unwrap();
+        std::fs::write(
+            common.join("logs/refs/stash"),
+            "0 1 A <a@b> 1 +0000\tWIP on main: 1f2e3d4 init\n\
+             1 2 A <a@b> 2 +0000\tOn feat: halfway\n\
+             2 3 A <a@b> 3 +0000\tWIP on feat: 1f2e3d4 init\n\
+             3 4 A <a@b> 4 +0000\tWIP on feature: 1f2e3d4 init\n\
+             4 5 A <a@b> 5 +0000\tWIP on (no branch): 1f2e3d4 init\n",
+        )
+        .unwrap();
+
+        assert_eq!(stash_count(&common, Some("main")), 1);
+        assert_eq!(stash_count(&linked, Some("feat")), 2);
+        assert_eq!(stash_count(&linked, None), 1);
+        assert_eq!(stash_count(&linked, Some("other")), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
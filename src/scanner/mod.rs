@@ -1,22 +1,220 @@
+pub mod aider;
 pub mod claude;
 pub mod codex;
+pub mod custom;
+pub mod gemini;
 pub mod git;
+pub mod pool;
+pub mod provider;
+pub mod timestamp;
 
-/// Identifies which AI assistant a session belongs to
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SessionProvider {
-    Claude,
-    Codex,
+use crate::config::Config;
+use crate::db::ScanStats;
+use claude::FileCheckpoint;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+
+// Re-export Session for convenience
+pub use claude::Session;
+pub use provider::{Provider, Registry};
+
+/// A single unit of work produced by a scan, sent back to the DB writer
+pub enum ScanResult {
+    Repo(git::Repo),
+    /// Sessions parsed from one file
+    Sessions(Vec<Session>),
+    /// Session whose file hasn't changed since its checkpoint (uuid)
+    Unchanged(String),
+    /// A repo that couldn't be read (its path) and why; it keeps what an
+    /// earlier scan recorded
+    RepoFailed(PathBuf, String),
+    /// A session file that failed to parse and why; the sessions an earlier
+    /// scan recorded from it (uuids) are kept as they were
+    SessionsFailed(Vec<String>, String),
+    /// A problem that didn't stop the scan, such as a provider whose session
+    /// files couldn't be listed
+    Warning(String),
+}
+
+/// Progress of a scan running in the background, as reported to the TUI
+pub enum ScanEvent {
+    /// Counts of repos and sessions done so far, out of the totals found during discovery
+    Progress {
+        repos_done: usize,
+        repos_total: usize,
+        sessions_done: usize,
+        sessions_total: usize,
+    },
+    Finished(Result<ScanStats, String>),
+    /// Another scan held the lock, so this one didn't run
+    Skipped,
+}
+
+enum ScanJob {
+    Repo(PathBuf),
+    Session(Arc<dyn Provider>, PathBuf, Vec<Session>),
+}
+
+/// True if `path` has the same size and mtime as when `previous` was parsed from it
+fn is_unchanged(path: &Path, previous: &Session) -> bool {
+    let Some(checkpoint) = &previous.checkpoint else {
+        return false;
+    };
+    claude::file_stamp(path)
+        .is_ok_and(|(size, modified)| checkpoint.size == size && checkpoint.modified == modified)
+}
+
+/// True if session `uuid` was recorded from a file other than `path` that
+/// still exists and was written after `path`. `path` is then a stale copy, such
+/// as the original a migrated session was copied from, and is left alone so the
+/// two files don't take turns as the session's file.
+fn is_stale_copy(recorded: &HashMap<String, FileCheckpoint>, uuid: &str, path: &Path) -> bool {
+    recorded.get(uuid).is_some_and(|checkpoint| {
+        checkpoint.path != path
+            && checkpoint.path.exists()
+            && claude::file_stamp(path).is_ok_and(|(_, modified)| modified <= checkpoint.modified)
+    })
+}
+
+/// Everything a scan needs to look at, discovered up front so the expensive
+/// part (git subprocesses, JSONL parsing) can be spread over a worker pool
+pub struct ScanPlan {
+    jobs: Vec<ScanJob>,
+    unchanged: Vec<String>,
+    /// Where each recorded session was last parsed from
+    recorded: HashMap<String, FileCheckpoint>,
+    /// Problems met during discovery, such as a provider whose sessions
+    /// couldn't be listed
+    warnings: Vec<String>,
 }
 
-impl SessionProvider {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            SessionProvider::Claude => "claude",
-            SessionProvider::Codex => "codex",
+impl ScanPlan {
+    /// Find repos under the configured directories and the session files of
+    /// every provider. `checkpoints` maps session files to what an earlier
+    /// scan parsed from them; unchanged files are skipped and the rest are
+    /// handed to their provider along with that earlier parse.
+    pub fn discover(
+        config: &Config,
+        providers: &Registry,
+        mut checkpoints: HashMap<PathBuf, Vec<Session>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut warnings = Vec::new();
+        let repos = git::find_repos(&config.scan_dirs, &mut warnings)?;
+        let projects: Vec<PathBuf> = repos
+            .iter()
+            .flat_map(|repo| git::worktree_paths(repo))
+            .chain(
+                checkpoints
+                    .values()
+                    .flatten()
+                    .filter(|s| !s.project_path.is_empty())
+                    .map(|s| PathBuf::from(&s.project_path)),
+            )
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mut jobs: Vec<ScanJob> = repos.into_iter().map(ScanJob::Repo).collect();
+        let recorded: HashMap<String, FileCheckpoint> = checkpoints
+            .values()
+            .flatten()
+            .filter_map(|s| Some((s.uuid.clone(), s.checkpoint.clone()?)))
+            .collect();
+
+        let mut unchanged = Vec::new();
+        for provider in providers.iter() {
+            let paths = match provider.session_files(&projects) {
+                Ok(paths) => paths,
+                Err(e) => {
+                    warnings.push(format!(
+                        "failed to list {} sessions: {}",
+                        provider.display_name(),
+                        e
+                    ));
+                    // Keep its recorded sessions rather than archive them as missing
+                    unchanged.extend(
+                        checkpoints
+                            .values()
+                            .flatten()
+                            .filter(|s| s.provider == provider.name())
+                            .map(|s| s.uuid.clone()),
+                    );
+                    continue;
+                }
+            };
+            for path in paths {
+                if let Some(uuid) = provider.session_id(&path)
+                    && is_stale_copy(&recorded, &uuid, &path)
+                {
+                    continue;
+                }
+                let previous = checkpoints.remove(&path).unwrap_or_default();
+                if !previous.is_empty() && previous.iter().all(|p| is_unchanged(&path, p)) {
+                    unchanged.extend(previous.into_iter().map(|p| p.uuid));
+                    continue;
+                }
+                jobs.push(ScanJob::Session(provider.clone(), path, previous));
+            }
         }
+
+        Ok(ScanPlan {
+            jobs,
+            unchanged,
+            recorded,
+            warnings,
+        })
     }
-}
 
-// Re-export Session for convenience
-pub use claude::Session;
+    pub fn repo_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| matches!(job, ScanJob::Repo(_)))
+            .count()
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.jobs.len() - self.repo_count() + self.unchanged.len()
+    }
+
+    /// Process every job on up to `jobs` threads, sending results as they complete
+    pub fn run(self, jobs: usize, results: Sender<ScanResult>) {
+        let unchanged = self.unchanged.into_iter().map(ScanResult::Unchanged);
+        let warnings = self.warnings.into_iter().map(ScanResult::Warning);
+        for result in unchanged.chain(warnings) {
+            if results.send(result).is_err() {
+                return;
+            }
+        }
+
+        pool::run(
+            self.jobs,
+            jobs,
+            |job| match job {
+                ScanJob::Repo(path) => Some(match git::scan_single_repo(&path) {
+                    Ok(repo) => ScanResult::Repo(repo),
+                    Err(e) => ScanResult::RepoFailed(
+                        path.canonicalize().unwrap_or_else(|_| path.clone()),
+                        format!("failed to scan repo {:?}: {}", path, e),
+                    ),
+                }),
+                ScanJob::Session(provider, path, previous) => {
+                    match provider.parse_sessions(&path, &previous) {
+                        Ok(mut sessions) => {
+                            sessions.retain(|s| !is_stale_copy(&self.recorded, &s.uuid, &path));
+                            Some(ScanResult::Sessions(sessions))
+                        }
+                        // Report but continue; one bad file shouldn't fail the scan,
+                        // nor archive what was read from it before
+                        Err(e) => Some(ScanResult::SessionsFailed(
+                            previous.into_iter().map(|p| p.uuid).collect(),
+                            format!("failed to parse {:?}: {}", path, e),
+                        )),
+                    }
+                }
+            },
+            results,
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+/// Number of workers to use when `--jobs` isn't given
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Run `work` over `items` on at most `jobs` worker threads.
+///
+/// Each `Some` result is sent to `results` as soon as it's ready, so the
+/// receiving side can write while scanning continues. Returns once every item
+/// has been processed, or early if the receiver hangs up.
+pub fn run<T, R, F>(items: Vec<T>, jobs: usize, work: F, results: Sender<R>)
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> Option<R> + Sync,
+{
+    let queue = Mutex::new(items.into_iter());
+    let work = &work;
+    let queue = &queue;
+
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1) {
+            let results = results.clone();
+            s.spawn(move || {
+                loop {
+                    // Hold the lock only long enough to take the next item
+                    let item = queue.lock().unwrap().next();
+                    let Some(item) = item else {
+                        break;
+                    };
+                    if let Some(result) = work(item)
+                        && results.send(result).is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,203 @@
+use super::aider::AiderProvider;
+use super::claude::{self, ClaudeProvider, Session, Turn};
+use super::codex::{self, CodexProvider};
+use super::custom::CustomProvider;
+use super::gemini::GeminiProvider;
+use crate::config::Config;
+use ratatui::style::Color;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// An agent CLI whose sessions ws lists, previews and resumes.
+///
+/// Supporting another agent means implementing this in its own module and
+/// adding it to `Registry::new`, or declaring it in the config for agents
+/// that write JSON (see `CustomProvider`).
+pub trait Provider: Send + Sync {
+    /// Stored with each session and matched by `provider:` filters
+    fn name(&self) -> &str;
+
+    /// The assistant as named in the preview and in untitled session tabs
+    fn display_name(&self) -> &str;
+
+    /// Every session file on disk. `projects` are the directories sessions
+    /// are known to run in, for agents that keep sessions per project: the
+    /// worktrees of the repos found by this scan and the cwds of sessions
+    /// already recorded.
+    fn session_files(&self, projects: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>>;
+
+    /// The id of the session in `path`, if the path alone tells, so a stale
+    /// copy of a recorded session can be skipped without reading it
+    fn session_id(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Parse the sessions in a file; most files hold one. `previous` is what
+    /// an earlier scan parsed from it, for providers that can pick up where
+    /// that parse left off; others re-read the whole file.
+    fn parse_sessions(
+        &self,
+        path: &Path,
+        previous: &[Session],
+    ) -> Result<Vec<Session>, Box<dyn Error>>;
+
+    /// The last `max_turns` turns of session `uuid` in `path`, for the preview
+    fn read_turns(
+        &self,
+        path: &Path,
+        uuid: &str,
+        max_turns: usize,
+    ) -> Result<Vec<Turn>, Box<dyn Error>>;
+
+    /// Shell command that resumes a session, run in the worktree it is
+    /// launched in. `path` is its session file and `home` the agent home it
+    /// was found under, if recorded.
+    fn resume_command(&self, uuid: &str, path: Option<&Path>, home: Option<&Path>) -> String;
+
+    /// Make a session that ran in `source` resumable from `target`. Agents
+    /// that resume by id from any directory have nothing to do.
+    fn migrate_session(
+        &self,
+        _uuid: &str,
+        _home: Option<&Path>,
+        _source: &Path,
+        _target: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Problems met while parsing that didn't fail a file, such as a shared
+    /// history file that couldn't be read
+    fn warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn style(&self) -> ProviderStyle;
+}
+
+/// Colors of a provider's sessions in the tree and its replies in the preview
+#[derive(Debug, Clone, Copy)]
+pub struct ProviderStyle {
+    /// Session titles and the assistant's name
+    pub color: Color,
+    /// Session metadata (message count, age)
+    pub dim_color: Color,
+}
+
+/// Shown for sessions of a provider that is no longer configured
+const UNKNOWN_STYLE: ProviderStyle = ProviderStyle {
+    color: Color::Gray,
+    dim_color: Color::DarkGray,
+};
+
+/// The providers a scan discovers sessions with, and that sessions are
+/// previewed and launched through, looked up by name
+#[derive(Clone)]
+pub struct Registry {
+    providers: Vec<Arc<dyn Provider>>,
+}
+
+impl Registry {
+    /// The built-in providers followed by those declared in the config
+    pub fn new(config: &Config) -> Self {
+        let mut providers: Vec<Arc<dyn Provider>> = vec![
+            Arc::new(ClaudeProvider::new(config.extra_homes(claude::NAME))),
+            Arc::new(CodexProvider::new(config.extra_homes(codex::NAME))),
+            Arc::new(GeminiProvider::new()),
+            Arc::new(AiderProvider),
+        ];
+        providers.extend(
+            config
+                .providers
+                .iter()
+                .map(|config| Arc::new(CustomProvider::new(config.clone())) as Arc<dyn Provider>),
+        );
+        Registry { providers }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Provider>> {
+        self.providers.iter()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Provider>> {
+        self.providers.iter().find(|p| p.name() == name)
+    }
+
+    /// Registered names, for error messages: `claude or codex`
+    pub fn describe(&self) -> String {
+        let names: Vec<&str> = self.providers.iter().map(|p| p.name()).collect();
+        match names.split_last() {
+            Some((last, [])) => last.to_string(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => String::new(),
+        }
+    }
+
+    pub fn style(&self, name: &str) -> ProviderStyle {
+        self.get(name).map_or(UNKNOWN_STYLE, |p| p.style())
+    }
+
+    /// Name of the assistant of a session, e.g. `Claude`
+    pub fn display_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.get(name).map_or(name, |p| p.display_name())
+    }
+}
+
+/// The home an agent uses when launched from here: the directory named by
+/// `env_var`, or else `default` under the home directory
+fn primary_home(env_var: &str, default: &str) -> Option<PathBuf> {
+    std::env::var(env_var)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| Config::expand_path(&dir))
+        .or_else(|| dirs::home_dir().map(|home| home.join(default)))
+}
+
+/// Where an agent keeps its sessions: its primary home (see `primary_home`)
+/// followed by `extra` homes from the config
+pub fn agent_homes(env_var: &str, default: &str, extra: &[String]) -> Vec<PathBuf> {
+    let primary = primary_home(env_var, default);
+
+    let mut homes: Vec<PathBuf> = Vec::new();
+    for home in primary
+        .into_iter()
+        .chain(extra.iter().map(|dir| Config::expand_path(dir)))
+    {
+        if !homes.contains(&home) {
+            homes.push(home);
+        }
+    }
+    homes
+}
+
+/// The home `path` was found under
+pub fn home_of<'a>(homes: &'a [PathBuf], path: &Path) -> Option<&'a PathBuf> {
+    homes.iter().find(|home| path.starts_with(home))
+}
+
+/// `command` with `env_var` set to `home`, unless the agent would use that
+/// home anyway, so a session launched from ws uses the home it came from
+pub fn with_home(command: String, env_var: &str, default: &str, home: Option<&Path>) -> String {
+    match home {
+        Some(home) if primary_home(env_var, default).as_deref() != Some(home) => {
+            format!(
+                "{}={} {}",
+                env_var,
+                shell_quote(&home.to_string_lossy()),
+                command
+            )
+        }
+        _ => command,
+    }
+}
+
+/// Quote `arg` for a POSIX shell unless it is plainly safe
+pub fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "/._-+:@=,".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+
+/// Epoch ms of an RFC 3339 date-time (`2024-05-20T10:15:32.120Z`,
+/// `2024-05-20T12:15:32+02:00`) or of one without a zone
+/// (`2024-05-20 10:15:32`), which is UTC, or local time if `local`. `None` if
+/// it doesn't parse.
+pub fn parse_ms(time: &str, local: bool) -> Option<i64> {
+    if local {
+        parse_in(time, &Local)
+    } else {
+        parse_in(time, &Utc)
+    }
+}
+
+/// `parse_ms` with times without a zone taken to be in `zone`
+fn parse_in<Tz: TimeZone>(time: &str, zone: &Tz) -> Option<i64> {
+    let time = time.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Some(time.timestamp_millis());
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())?;
+    // A time skipped by a DST change doesn't exist; one repeated takes its first
+    zone.from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn parses_utc_times() {
+        assert_eq!(parse_ms("1970-01-01T00:00:00Z", false), Some(0));
+        assert_eq!(
+            parse_ms("2024-05-20T10:15:32.120Z", false),
+            Some(1_716_200_132_120)
+        );
+        assert_eq!(
+            parse_ms("2024-05-20 10:15:32", false),
+            Some(1_716_200_132_000)
+        );
+        assert_eq!(
+            parse_ms("2024-05-20T10:15:32.5", false),
+            Some(1_716_200_132_500)
+        );
+    }
+
+    #[test]
+    fn honors_explicit_offsets() {
+        let utc = Some(1_716_200_132_000);
+        assert_eq!(parse_ms("2024-05-20T12:15:32+02:00", false), utc);
+        assert_eq!(parse_ms("2024-05-20T12:15:32+02:00", true), utc);
+        assert_eq!(parse_ms("2024-05-20T10:15:32Z", true), utc);
+    }
+
+    #[test]
+    fn reads_times_without_a_zone_in_the_given_zone() {
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(
+            parse_in("2024-05-20 12:15:32", &cest),
+            Some(1_716_200_132_000)
+        );
+        assert_eq!(
+            parse_in("2024-05-20T12:15:32+00:00", &cest),
+            Some(1_716_207_332_000)
+        );
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse_ms("yesterday", false), None);
+        assert_eq!(parse_ms("2024-05-20", false), None);
+        assert_eq!(parse_ms("", true), None);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config::Config;
+use crate::db::{ModelUsage, UsageGroup, UsageRow};
+use crate::scanner::claude::TokenUsage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Usage and estimated cost of one group of sessions
+#[derive(Default)]
+struct Totals {
+    sessions: HashSet<String>,
+    tokens: TokenUsage,
+    cost: f64,
+}
+
+/// Print token usage and estimated cost per group, with a total line.
+/// Repos and branches are listed most expensive first, days in order.
+pub fn print_report(group: UsageGroup, rows: Vec<UsageRow>, config: &Config) {
+    if rows.is_empty() {
+        println!("No token usage recorded");
+        return;
+    }
+
+    let mut groups: HashMap<String, Totals> = HashMap::new();
+    let mut total = Totals::default();
+    let mut unpriced = BTreeSet::new();
+    for row in rows {
+        let cost = config.cost(&row.model, &row.tokens);
+        if cost.is_none() && row.tokens.total() > 0 {
+            unpriced.insert(row.model.clone());
+        }
+        for totals in [groups.entry(row.group).or_default(), &mut total] {
+            totals.sessions.insert(row.uuid.clone());
+            totals.tokens.add(&row.tokens);
+            totals.cost += cost.unwrap_or(0.0);
+        }
+    }
+
+    let mut groups: Vec<(String, Totals)> = groups.into_iter().collect();
+    match group {
+        UsageGroup::Day => groups.sort_by(|a, b| a.0.cmp(&b.0)),
+        UsageGroup::Repo | UsageGroup::Branch => {
+            groups.sort_by(|a, b| b.1.cost.total_cmp(&a.1.cost).then_with(|| a.0.cmp(&b.0)))
+        }
+    }
+
+    let heading = match group {
+        UsageGroup::Repo => "Repo",
+        UsageGroup::Branch => "Branch",
+        UsageGroup::Day => "Day",
+    };
+    let width = groups
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .chain([heading.len(), "Total".len()])
+        .max()
+        .unwrap_or(0);
+    let line = |name: &str, totals: &Totals| {
+        println!(
+            "{:<width$}  {:>8}  {:>7}  {:>7}  {:>11}  {:>10}  {:>9}",
+            name,
+            totals.sessions.len(),
+            format_tokens(totals.tokens.input),
+            format_tokens(totals.tokens.output),
+            format_tokens(totals.tokens.cache_creation),
+            format_tokens(totals.tokens.cache_read),
+            format_cost(totals.cost),
+        );
+    };
+
+    println!(
+        "{:<width$}  {:>8}  {:>7}  {:>7}  {:>11}  {:>10}  {:>9}",
+        heading, "Sessions", "Input", "Output", "Cache write", "Cache read", "Cost"
+    );
+    for (name, totals) in &groups {
+        line(name, totals);
+    }
+    if groups.len() > 1 {
+        line("Total", &total);
+    }
+
+    if !unpriced.is_empty() {
+        println!();
+        println!(
+            "No price for {}; their tokens are left out of the cost. Add them under [prices] in config.toml.",
+            unpriced.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+}
+
+/// Estimated cost of a session's usage, or `None` if a model it used has no price
+pub fn session_cost(usage: &[ModelUsage], config: &Config) -> Option<f64> {
+    usage.iter().map(|u| config.cost(&u.model, &u.tokens)).sum()
+}
+
+/// A token count in the largest fitting unit, e.g. `950`, `12.3k` or `1.2M`
+pub fn format_tokens(count: i64) -> String {
+    match count {
+        0..1_000 => count.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", count as f64 / 1_000.0),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
+pub fn format_cost(cost: f64) -> String {
+    format!("${:.2}", cost)
+}
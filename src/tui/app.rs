@@ -1,12 +1,21 @@
 use crate::actions;
-use crate::config::Config;
-use crate::db::{BranchData, Database, RepoData};
-use crate::migrate;
-use crate::scanner::git::Worktree;
+use crate::config::{Config, RecentWindow};
+use crate::db::{BranchData, Database, RepoData, SessionData};
+use crate::query::{Query, SqlFilter};
+use crate::scanner::git::{WIP_COMMIT_SUBJECT, WorktreeStatus};
+use crate::scanner::{Registry, ScanEvent};
+use crate::tui::filter::{self, FilteredRepo};
+use crate::tui::transcript_cache::TranscriptCache;
+use crate::tui::worktree_cache::WorktreeCache;
 use crossterm::event::KeyCode;
-use std::collections::HashSet;
+use ratatui::layout::Rect;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+
+/// Filter prefix that switches the tree to full-text search over transcripts
+pub const TEXT_SEARCH_PREFIX: &str = "/text";
 
 pub enum Action {
     Continue,
@@ -26,26 +35,30 @@ pub struct PendingLaunch {
 pub struct RepoNode {
     pub data: RepoData,
     pub branches: Vec<BranchNode>,
-    pub worktree_states: Vec<WorktreeState>, // Runtime state for each worktree
     pub expanded: bool,
+    pub name_matches: Vec<usize>, // Chars of the name matched by the filter
 }
 
 pub struct BranchNode {
     pub selected_worktree_idx: usize, // Index into repo's worktrees
     pub selected_sessions: HashSet<String>, // UUIDs of selected sessions
     pub expanded: bool,
-}
-
-#[derive(Clone)]
-pub struct WorktreeState {
-    pub is_dirty: bool,
-    pub has_wip: bool,
+    pub expanded_chains: HashSet<String>, // UUIDs of chain heads showing the rest of their chain
+    pub name_matches: Vec<usize>,         // Chars of the name matched by the filter
+    pub session_matches: Vec<Vec<usize>>, // Chars of each session title matched by the filter
 }
 
 pub struct App {
     pub db: Database,
     pub config: Config,
+    pub providers: Registry,
     pub filter: String,
+    pub filter_error: Option<String>, // Why the filter doesn't parse; the tree keeps the last valid one
+    query: Query,                     // Last filter that parsed
+    loaded_sql: Option<SqlFilter>,    // SQL terms `all_repos` was loaded with
+    pub show_archived: bool,
+    pub recent_window: RecentWindow,
+    all_repos: Vec<RepoData>, // Matches of the filter's SQL terms; `repos` is what the rest leaves
     pub repos: Vec<RepoNode>,
     pub selected_repo_idx: usize,
     pub selected_branch_idx: usize,
@@ -54,21 +67,49 @@ pub struct App {
     pub pending_launch: PendingLaunch,
     pub scroll_offset: u16,
     pub viewport_height: u16,
+    pub worktree_cache: WorktreeCache,
+    pub show_preview: bool,
+    pub preview_area: Rect, // Where the preview pane was last drawn, for mouse scrolling
+    pub preview_max_scroll: u16,
+    preview_scroll: Option<(String, u16)>, // Lines scrolled up from the end, by session uuid
+    preview_files: Option<(String, Vec<String>)>, // Files written by the previewed session, by uuid
+    pub transcript_cache: TranscriptCache,
+    pub scan_status: Option<ScanEvent>, // Latest event from the background scan
+    scan_events: Option<Receiver<ScanEvent>>,
+}
+
+struct SelectionKey {
+    repo: PathBuf,
+    branch: Option<String>,
+    session: Option<String>,
 }
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum SelectedItem {
     Repo,
     Branch,
-    Session(usize), // Index within branch's sessions
+    Session(usize),        // Index within branch's sessions
+    Lineage(usize, usize), // Index within branch's sessions, then within its lineage
 }
 
 impl App {
-    pub fn new(db: Database, config: Config, filter: String) -> Result<Self, Box<dyn Error>> {
+    pub fn new(
+        db: Database,
+        config: Config,
+        filter: String,
+        scan_events: Option<Receiver<ScanEvent>>,
+    ) -> Result<Self, Box<dyn Error>> {
         let mut app = App {
             db,
+            recent_window: config.recent_window,
+            providers: Registry::new(&config),
             config,
             filter,
+            filter_error: None,
+            query: Query::default(),
+            loaded_sql: None,
+            show_archived: false,
+            all_repos: Vec::new(),
             repos: Vec::new(),
             selected_repo_idx: 0,
             selected_branch_idx: 0,
@@ -77,52 +118,128 @@ impl App {
             pending_launch: PendingLaunch::default(),
             scroll_offset: 0,
             viewport_height: 0,
+            worktree_cache: WorktreeCache::new(),
+            show_preview: false,
+            preview_area: Rect::default(),
+            preview_max_scroll: 0,
+            preview_scroll: None,
+            preview_files: None,
+            transcript_cache: TranscriptCache::new(),
+            scan_status: scan_events.as_ref().map(|_| ScanEvent::Progress {
+                repos_done: 0,
+                repos_total: 0,
+                sessions_done: 0,
+                sessions_total: 0,
+            }),
+            scan_events,
         };
 
         app.refresh_data()?;
         Ok(app)
     }
 
+    /// Reload the tree from the database, then re-apply the filter
     fn refresh_data(&mut self) -> Result<(), Box<dyn Error>> {
-        let repo_data = self.db.get_repos_with_data(&self.filter)?;
+        self.loaded_sql = None;
+        self.preview_files = None;
+        self.apply_filter()
+    }
+
+    /// Rebuild the visible tree for the current filter. The database is only
+    /// queried when the filter's SQL terms change; fuzzy text and `dirty` run
+    /// in memory on every keystroke. Text search goes to the FTS index.
+    fn apply_filter(&mut self) -> Result<(), Box<dyn Error>> {
+        let repo_data = match self.text_query() {
+            Some(query) => {
+                let hits = self.db.get_repos_for_search(query, self.show_archived)?;
+                self.filter_error = None;
+                hits.into_iter().map(FilteredRepo::unmatched).collect()
+            }
+            None => {
+                self.query = match Query::parse(&self.filter, &self.providers) {
+                    Ok(query) => query,
+                    Err(e) => {
+                        self.filter_error = Some(e);
+                        return Ok(());
+                    }
+                };
+                self.filter_error = None;
+
+                let sql = self.query.to_sql(1);
+                if self.loaded_sql.as_ref() != Some(&sql) {
+                    self.all_repos = self
+                        .db
+                        .get_repos_with_data(&self.query, self.show_archived)?;
+                    self.loaded_sql = Some(sql);
+                }
+
+                let cache = &self.worktree_cache;
+                filter::filter_tree(
+                    &self.all_repos,
+                    &self.query,
+                    self.recent_window.cutoff_ms(),
+                    |path| cache.get(path).is_some_and(|status| status.is_dirty()),
+                )
+            }
+        };
+
+        // Keep selection and expand/worktree/session choices for nodes that survive the reload
+        let selection = self.selection_key();
+        let mut previous: HashMap<PathBuf, RepoNode> = self
+            .repos
+            .drain(..)
+            .map(|repo| (repo.data.path.clone(), repo))
+            .collect();
 
         self.repos = repo_data
             .into_iter()
-            .map(|data| {
-                // Compute worktree states for all worktrees in this repo
-                let worktree_states: Vec<WorktreeState> = data
-                    .worktrees
-                    .iter()
-                    .map(|wt| {
-                        let worktree = Worktree {
-                            path: wt.path.clone(),
-                            branch: wt.checked_out_branch.clone(),
-                        };
-                        WorktreeState {
-                            is_dirty: worktree.is_dirty(),
-                            has_wip: worktree.has_wip_commit(),
-                        }
-                    })
-                    .collect();
+            .map(|filtered| {
+                let data = filtered.data;
+                let prev_repo = previous.remove(&data.path);
 
                 let branches: Vec<BranchNode> = data
                     .branches
                     .iter()
-                    .map(|branch_data| {
-                        // Pre-select worktree where this branch is checked out
-                        let selected_worktree_idx = data
-                            .worktrees
-                            .iter()
-                            .position(|wt| {
-                                wt.checked_out_branch
-                                    .as_ref()
-                                    .map_or(false, |b| b == &branch_data.branch)
+                    .zip(filtered.branches)
+                    .map(|(branch_data, matches)| {
+                        let prev = prev_repo.as_ref().and_then(|repo| {
+                            let idx = repo
+                                .data
+                                .branches
+                                .iter()
+                                .position(|b| b.branch == branch_data.branch)?;
+                            let worktree_path = repo
+                                .data
+                                .worktrees
+                                .get(repo.branches[idx].selected_worktree_idx)
+                                .map(|wt| &wt.path);
+                            Some((&repo.branches[idx], worktree_path))
+                        });
+
+                        // Keep the previously chosen worktree, otherwise pre-select
+                        // the worktree where this branch is checked out
+                        let selected_worktree_idx = prev
+                            .and_then(|(_, path)| {
+                                data.worktrees.iter().position(|wt| Some(&wt.path) == path)
+                            })
+                            .or_else(|| {
+                                data.worktrees.iter().position(|wt| {
+                                    wt.checked_out_branch.as_ref() == Some(&branch_data.branch)
+                                })
                             })
                             .unwrap_or(0);
+
                         BranchNode {
                             selected_worktree_idx,
-                            selected_sessions: HashSet::new(),
-                            expanded: true,
+                            selected_sessions: prev
+                                .map(|(b, _)| b.selected_sessions.clone())
+                                .unwrap_or_default(),
+                            expanded: prev.is_none_or(|(b, _)| b.expanded),
+                            expanded_chains: prev
+                                .map(|(b, _)| b.expanded_chains.clone())
+                                .unwrap_or_default(),
+                            name_matches: matches.name_matches,
+                            session_matches: matches.session_matches,
                         }
                     })
                     .collect();
@@ -130,23 +247,259 @@ impl App {
                 RepoNode {
                     data,
                     branches,
-                    worktree_states,
-                    expanded: true,
+                    expanded: prev_repo.is_none_or(|r| r.expanded),
+                    name_matches: filtered.name_matches,
                 }
             })
             .collect();
 
+        // Statuses are filled in by the background workers as they finish
+        self.request_worktree_statuses();
+
+        self.restore_selection(selection);
+
+        Ok(())
+    }
+
+    /// The search terms if the filter is a `/text ...` search
+    pub fn text_query(&self) -> Option<&str> {
+        self.filter
+            .strip_prefix(TEXT_SEARCH_PREFIX)
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+            .map(str::trim)
+    }
+
+    /// Identify the selected node by repo path, branch name and session uuid
+    fn selection_key(&self) -> Option<SelectionKey> {
+        let repo = self.current_repo()?;
+        let branch = match self.selected_item {
+            SelectedItem::Repo => None,
+            _ => self.current_branch_data(),
+        };
+        let session = self.selected_session();
+
+        Some(SelectionKey {
+            repo: repo.data.path.clone(),
+            branch: branch.map(|b| b.branch.clone()),
+            session: session.map(|s| s.uuid.clone()),
+        })
+    }
+
+    /// Re-select the node identified by `key`, or the nearest position still in bounds
+    fn restore_selection(&mut self, key: Option<SelectionKey>) {
+        if let Some(key) = key
+            && let Some(repo_idx) = self.repos.iter().position(|r| r.data.path == key.repo)
+        {
+            self.selected_repo_idx = repo_idx;
+            let branches = &self.repos[repo_idx].data.branches;
+            let branch_idx = key
+                .branch
+                .and_then(|name| branches.iter().position(|b| b.branch == name));
+
+            match branch_idx {
+                Some(branch_idx) => {
+                    self.selected_branch_idx = branch_idx;
+                    let sessions = &branches[branch_idx].sessions;
+                    let expanded_chains =
+                        &self.repos[repo_idx].branches[branch_idx].expanded_chains;
+                    self.selected_item = key
+                        .session
+                        .and_then(|uuid| {
+                            sessions.iter().enumerate().find_map(|(idx, s)| {
+                                if s.uuid == uuid {
+                                    return Some(SelectedItem::Session(idx));
+                                }
+                                // A session of a collapsed chain selects the chain
+                                let member = s.lineage.iter().position(|m| m.uuid == uuid)?;
+                                Some(if expanded_chains.contains(&s.uuid) {
+                                    SelectedItem::Lineage(idx, member)
+                                } else {
+                                    SelectedItem::Session(idx)
+                                })
+                            })
+                        })
+                        .unwrap_or(SelectedItem::Branch);
+                }
+                None => {
+                    self.selected_branch_idx = 0;
+                    self.selected_item = SelectedItem::Repo;
+                }
+            }
+            return;
+        }
+
         // Reset selection if out of bounds
         if self.selected_repo_idx >= self.repos.len() {
             self.selected_repo_idx = 0;
         }
-        if let Some(repo) = self.repos.get(self.selected_repo_idx) {
-            if self.selected_branch_idx >= repo.branches.len() {
-                self.selected_branch_idx = 0;
+        if let Some(repo) = self.repos.get(self.selected_repo_idx)
+            && self.selected_branch_idx >= repo.branches.len()
+        {
+            self.selected_branch_idx = 0;
+            self.selected_item = SelectedItem::Repo;
+        }
+        if let SelectedItem::Session(idx) | SelectedItem::Lineage(idx, _) = self.selected_item
+            && self
+                .current_branch_data()
+                .is_none_or(|bd| idx >= bd.sessions.len())
+        {
+            self.selected_item = SelectedItem::Branch;
+        }
+        if matches!(self.selected_item, SelectedItem::Lineage(..))
+            && self.selected_session().is_none()
+        {
+            self.selected_item = SelectedItem::Branch;
+        }
+    }
+
+    /// Queue background status refreshes for visible worktrees that are missing or stale.
+    /// A `dirty` filter needs them for hidden repos too.
+    fn request_worktree_statuses(&mut self) {
+        let repos: Vec<&RepoData> = if self.filters_dirty() {
+            self.all_repos.iter().collect()
+        } else {
+            self.repos.iter().map(|repo| &repo.data).collect()
+        };
+        let paths = repos
+            .iter()
+            .flat_map(|repo| repo.worktrees.iter().map(|wt| wt.path.as_path()));
+        self.worktree_cache.request(paths);
+    }
+
+    fn filters_dirty(&self) -> bool {
+        self.text_query().is_none() && self.query.dirty().next().is_some()
+    }
+
+    /// Pick up finished background work. Returns true if the tree needs a redraw.
+    pub fn poll_background(&mut self) -> bool {
+        let mut changed = self.worktree_cache.poll();
+
+        // Only read a transcript once navigation pauses on its session
+        self.request_preview();
+        changed |= self.transcript_cache.poll();
+
+        // New statuses can change what a `dirty` filter shows
+        if changed && self.filters_dirty() {
+            let _ = self.apply_filter();
+        }
+
+        if let Some(events) = &self.scan_events {
+            let mut done = false;
+            loop {
+                match events.try_recv() {
+                    Ok(event) => {
+                        done |= matches!(event, ScanEvent::Finished(_) | ScanEvent::Skipped);
+                        self.scan_status = Some(event);
+                        changed = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        done = true;
+                        break;
+                    }
+                }
+            }
+
+            // Reload the tree in place once the scan has written everything
+            if done {
+                self.scan_events = None;
+                let _ = self.refresh_data();
+                changed = true;
             }
         }
 
-        Ok(())
+        self.request_worktree_statuses();
+        changed
+    }
+
+    /// Switch to the next recent window preset (today, week, month, all)
+    pub fn cycle_recent_window(&mut self) {
+        self.recent_window = self.recent_window.next();
+        let _ = self.apply_filter();
+        self.ensure_selection_visible();
+    }
+
+    /// Show or hide the transcript preview of the selected session
+    pub fn toggle_preview(&mut self) {
+        self.show_preview = !self.show_preview;
+        self.request_preview();
+    }
+
+    /// The selected session, if a session (rather than a repo or branch) is selected
+    pub fn selected_session(&self) -> Option<&SessionData> {
+        match self.selected_item {
+            SelectedItem::Session(idx) => self.current_branch_data()?.sessions.get(idx),
+            SelectedItem::Lineage(idx, member) => self
+                .current_branch_data()?
+                .sessions
+                .get(idx)?
+                .lineage
+                .get(member),
+            _ => None,
+        }
+    }
+
+    fn request_preview(&mut self) {
+        if !self.show_preview {
+            return;
+        }
+        let Some(session) = self.selected_session() else {
+            return;
+        };
+        let uuid = session.uuid.clone();
+        if let (Some(path), Some(provider)) = (
+            session.file_path.clone(),
+            self.providers.get(&session.provider),
+        ) {
+            self.transcript_cache
+                .request(&uuid, &path, provider.clone());
+        }
+
+        if self
+            .preview_files
+            .as_ref()
+            .is_none_or(|(loaded, _)| *loaded != uuid)
+        {
+            let files = self.db.get_session_files(&uuid).unwrap_or_default();
+            self.preview_files = Some((uuid, files));
+        }
+    }
+
+    /// Files the selected session wrote, once loaded for the preview
+    pub fn preview_files(&self) -> &[String] {
+        match (&self.preview_files, self.selected_session()) {
+            (Some((uuid, files)), Some(session)) if *uuid == session.uuid => files,
+            _ => &[],
+        }
+    }
+
+    /// Lines the preview is scrolled up from the end of the selected transcript
+    pub fn preview_scroll(&self) -> u16 {
+        match (&self.preview_scroll, self.selected_session()) {
+            (Some((uuid, lines)), Some(session)) if *uuid == session.uuid => *lines,
+            _ => 0,
+        }
+    }
+
+    /// Scroll the preview by `delta` lines; positive scrolls back in time
+    pub fn scroll_preview(&mut self, delta: i32) {
+        let Some(uuid) = self.selected_session().map(|s| s.uuid.clone()) else {
+            return;
+        };
+        let lines = (self.preview_scroll() as i32 + delta).clamp(0, self.preview_max_scroll as i32);
+        self.preview_scroll = Some((uuid, lines as u16));
+    }
+
+    /// Show or hide repos and sessions that have gone missing since they were scanned
+    pub fn toggle_archived(&mut self) {
+        self.show_archived = !self.show_archived;
+        let _ = self.refresh_data();
+        self.ensure_selection_visible();
+    }
+
+    /// Cached status of a worktree; empty until the first background refresh lands
+    pub fn worktree_status(&self, path: &Path) -> WorktreeStatus {
+        self.worktree_cache.get(path).cloned().unwrap_or_default()
     }
 
     pub fn handle_key(&mut self, key: KeyCode) -> Action {
@@ -174,26 +527,36 @@ impl App {
                 self.cycle_worktree(1);
                 Action::Continue
             }
+            // Search terms are separated by spaces, so Space types while searching text
+            KeyCode::Char(' ') if self.filter.starts_with(TEXT_SEARCH_PREFIX) => {
+                self.filter.push(' ');
+                let _ = self.apply_filter();
+                Action::Continue
+            }
             KeyCode::Char(' ') => {
                 self.toggle_session();
                 Action::Continue
             }
             KeyCode::Enter => self.confirm_selection(),
+            KeyCode::Tab => {
+                self.toggle_chain();
+                Action::Continue
+            }
             KeyCode::Esc => {
                 if !self.filter.is_empty() {
                     self.filter.clear();
-                    let _ = self.refresh_data();
+                    let _ = self.apply_filter();
                 }
                 Action::Continue
             }
             KeyCode::Backspace => {
                 self.filter.pop();
-                let _ = self.refresh_data();
+                let _ = self.apply_filter();
                 Action::Continue
             }
             KeyCode::Char(c) => {
                 self.filter.push(c);
-                let _ = self.refresh_data();
+                let _ = self.apply_filter();
                 Action::Continue
             }
             _ => Action::Continue,
@@ -204,9 +567,10 @@ impl App {
         match key {
             KeyCode::Char('y') | KeyCode::Char('Y') => {
                 // Add WIP commit command
-                self.pending_launch
-                    .pre_commands
-                    .push("git add -A && git commit -m 'WIP: paused work'".to_string());
+                self.pending_launch.pre_commands.push(format!(
+                    "git add -A && git commit -m '{}'",
+                    WIP_COMMIT_SUBJECT
+                ));
                 self.confirm_dialog = None;
                 // Proceed with launch
                 self.do_launch()
@@ -259,19 +623,16 @@ impl App {
                 if self.selected_repo_idx > 0 {
                     self.selected_repo_idx -= 1;
                     // Move to last item of previous repo if expanded
-                    if let Some(repo) = self.repos.get(self.selected_repo_idx) {
-                        if repo.expanded && !repo.branches.is_empty() {
-                            let last_branch_idx = repo.branches.len() - 1;
-                            let branch = &repo.branches[last_branch_idx];
-                            let branch_data = &repo.data.branches[last_branch_idx];
-                            self.selected_branch_idx = last_branch_idx;
-                            if branch.expanded && !branch_data.sessions.is_empty() {
-                                self.selected_item =
-                                    SelectedItem::Session(branch_data.sessions.len() - 1);
-                            } else {
-                                self.selected_item = SelectedItem::Branch;
-                            }
-                        }
+                    if let Some(repo) = self.repos.get(self.selected_repo_idx)
+                        && repo.expanded
+                        && !repo.branches.is_empty()
+                    {
+                        let last_branch_idx = repo.branches.len() - 1;
+                        self.selected_branch_idx = last_branch_idx;
+                        self.selected_item = last_item(
+                            &repo.branches[last_branch_idx],
+                            &repo.data.branches[last_branch_idx],
+                        );
                     }
                 }
             }
@@ -282,10 +643,7 @@ impl App {
                     if let (Some(branch), Some(branch_data)) =
                         (self.current_branch(), self.current_branch_data())
                     {
-                        if branch.expanded && !branch_data.sessions.is_empty() {
-                            self.selected_item =
-                                SelectedItem::Session(branch_data.sessions.len() - 1);
-                        }
+                        self.selected_item = last_item(branch, branch_data);
                     }
                 } else {
                     // Move to repo
@@ -294,11 +652,24 @@ impl App {
             }
             SelectedItem::Session(idx) => {
                 if idx > 0 {
-                    self.selected_item = SelectedItem::Session(idx - 1);
+                    // Into the end of the previous chain if it is expanded
+                    self.selected_item = match (self.current_branch(), self.current_branch_data()) {
+                        (Some(branch), Some(branch_data)) => {
+                            session_item(branch, &branch_data.sessions[idx - 1], idx - 1)
+                        }
+                        _ => SelectedItem::Session(idx - 1),
+                    };
                 } else {
                     self.selected_item = SelectedItem::Branch;
                 }
             }
+            SelectedItem::Lineage(idx, member) => {
+                self.selected_item = if member > 0 {
+                    SelectedItem::Lineage(idx, member - 1)
+                } else {
+                    SelectedItem::Session(idx)
+                };
+            }
         }
     }
 
@@ -319,10 +690,10 @@ impl App {
                 }
             }
             SelectedItem::Branch => {
-                let should_go_to_session = self.current_branch().map_or(false, |b| b.expanded)
+                let should_go_to_session = self.current_branch().is_some_and(|b| b.expanded)
                     && self
                         .current_branch_data()
-                        .map_or(false, |bd| !bd.sessions.is_empty());
+                        .is_some_and(|bd| !bd.sessions.is_empty());
 
                 if should_go_to_session {
                     self.selected_item = SelectedItem::Session(0);
@@ -336,7 +707,17 @@ impl App {
                     }
                 }
             }
-            SelectedItem::Session(idx) => {
+            SelectedItem::Session(idx) if self.chain_expanded(idx) => {
+                self.selected_item = SelectedItem::Lineage(idx, 0);
+            }
+            SelectedItem::Lineage(idx, member)
+                if self
+                    .current_branch_data()
+                    .is_some_and(|bd| member + 1 < bd.sessions[idx].lineage.len()) =>
+            {
+                self.selected_item = SelectedItem::Lineage(idx, member + 1);
+            }
+            SelectedItem::Session(idx) | SelectedItem::Lineage(idx, _) => {
                 let sessions_len = self.current_branch_data().map_or(0, |bd| bd.sessions.len());
                 if idx < sessions_len - 1 {
                     self.selected_item = SelectedItem::Session(idx + 1);
@@ -416,13 +797,25 @@ impl App {
 
                     if branch.expanded {
                         let branch_data = &repo.data.branches[branch_idx];
-                        for (session_idx, _) in branch_data.sessions.iter().enumerate() {
+                        for (session_idx, session) in branch_data.sessions.iter().enumerate() {
                             if is_selected_branch
                                 && self.selected_item == SelectedItem::Session(session_idx)
                             {
                                 return line;
                             }
                             line += 1;
+
+                            if branch.expanded_chains.contains(&session.uuid) {
+                                for member_idx in 0..session.lineage.len() {
+                                    if is_selected_branch
+                                        && self.selected_item
+                                            == SelectedItem::Lineage(session_idx, member_idx)
+                                    {
+                                        return line;
+                                    }
+                                    line += 1;
+                                }
+                            }
                         }
                     }
                 }
@@ -466,25 +859,50 @@ impl App {
     }
 
     fn toggle_session(&mut self) {
-        if let SelectedItem::Session(idx) = self.selected_item {
-            // Get session UUID from branch data
-            let uuid = self
-                .current_branch_data()
-                .and_then(|bd| bd.sessions.get(idx))
-                .map(|s| s.uuid.clone());
-
-            if let Some(uuid) = uuid {
-                if let Some(branch) = self.current_branch_mut() {
-                    if branch.selected_sessions.contains(&uuid) {
-                        branch.selected_sessions.remove(&uuid);
-                    } else {
-                        branch.selected_sessions.insert(uuid);
-                    }
-                }
+        let uuid = self.selected_session().map(|s| s.uuid.clone());
+        if let Some(uuid) = uuid
+            && let Some(branch) = self.current_branch_mut()
+        {
+            if branch.selected_sessions.contains(&uuid) {
+                branch.selected_sessions.remove(&uuid);
+            } else {
+                branch.selected_sessions.insert(uuid);
             }
         }
     }
 
+    /// Whether the session at `idx` is a chain showing the rest of its sessions
+    fn chain_expanded(&self, idx: usize) -> bool {
+        self.current_branch()
+            .zip(self.current_branch_data())
+            .is_some_and(|(branch, branch_data)| {
+                let session = &branch_data.sessions[idx];
+                !session.lineage.is_empty() && branch.expanded_chains.contains(&session.uuid)
+            })
+    }
+
+    /// Expand or collapse the selected session's chain
+    fn toggle_chain(&mut self) {
+        let (SelectedItem::Session(idx) | SelectedItem::Lineage(idx, _)) = self.selected_item
+        else {
+            return;
+        };
+        let Some(head) = self.current_branch_data().map(|bd| &bd.sessions[idx]) else {
+            return;
+        };
+        if head.lineage.is_empty() {
+            return;
+        }
+        let uuid = head.uuid.clone();
+        if let Some(branch) = self.current_branch_mut()
+            && !branch.expanded_chains.remove(&uuid)
+        {
+            branch.expanded_chains.insert(uuid);
+        }
+        self.selected_item = SelectedItem::Session(idx);
+        self.ensure_selection_visible();
+    }
+
     fn toggle_expand(&mut self) {
         match self.selected_item {
             SelectedItem::Repo => {
@@ -507,9 +925,9 @@ impl App {
                 self.toggle_expand();
                 Action::Continue
             }
-            SelectedItem::Branch | SelectedItem::Session(_) => {
+            SelectedItem::Branch | SelectedItem::Session(_) | SelectedItem::Lineage(..) => {
                 // Extract needed state before modifying self
-                let (has_wip, is_dirty, worktree_name, branch_name, checked_out_branch) = {
+                let (worktree_path, worktree_name, branch_name, checked_out_branch) = {
                     let repo = match self.current_repo() {
                         Some(r) => r,
                         None => return Action::Continue,
@@ -525,19 +943,22 @@ impl App {
                     };
 
                     let wt_idx = branch_node.selected_worktree_idx;
-                    let state = &repo.worktree_states[wt_idx];
                     let worktree = &repo.data.worktrees[wt_idx];
                     let branch_data = &repo.data.branches[self.selected_branch_idx];
 
                     (
-                        state.has_wip,
-                        state.is_dirty,
+                        worktree.path.clone(),
                         worktree.name.clone(),
                         branch_data.branch.clone(),
                         worktree.checked_out_branch.clone(),
                     )
                 };
 
+                // Don't trust the cache here: the launch commands depend on the current state
+                let status = self.worktree_cache.refresh_now(&worktree_path);
+                let has_wip = status.has_wip_commit();
+                let is_dirty = status.is_dirty();
+
                 // Reset pending commands
                 self.pending_launch = PendingLaunch::default();
 
@@ -609,15 +1030,25 @@ impl App {
         }
 
         let mut first_session = true;
+        // Sessions of a chain can be selected as well as its newest leaf
+        let sessions: Vec<&SessionData> = branch_data
+            .sessions
+            .iter()
+            .flat_map(|s| std::iter::once(s).chain(&s.lineage))
+            .collect();
         for uuid in &branch.selected_sessions {
-            if let Some(session) = branch_data.sessions.iter().find(|s| &s.uuid == uuid) {
-                // Only migrate Claude sessions - Codex uses central storage
-                if session.provider == "claude" {
-                    let source_path = PathBuf::from(&session.project_path);
-                    if source_path != worktree.path {
-                        let _ =
-                            migrate::migrate_session(&session.uuid, &source_path, &worktree.path);
-                    }
+            if let Some(session) = sessions.iter().find(|s| &s.uuid == uuid) {
+                let Some(provider) = self.providers.get(&session.provider) else {
+                    continue;
+                };
+                let source_path = PathBuf::from(&session.project_path);
+                if source_path != worktree.path {
+                    let _ = provider.migrate_session(
+                        &session.uuid,
+                        session.home.as_deref(),
+                        &source_path,
+                        &worktree.path,
+                    );
                 }
 
                 let title = session
@@ -625,13 +1056,7 @@ impl App {
                     .as_ref()
                     .or(session.first_prompt.as_ref())
                     .map(|s| truncate(s, 30))
-                    .unwrap_or_else(|| {
-                        if session.provider == "codex" {
-                            "Codex session".to_string()
-                        } else {
-                            "Claude session".to_string()
-                        }
-                    });
+                    .unwrap_or_else(|| format!("{} session", provider.display_name()));
 
                 // Inject pre_commands (checkout, WIP reset) into the first session tab
                 let cmds = if first_session {
@@ -644,7 +1069,11 @@ impl App {
                     &session.uuid,
                     &worktree.path,
                     &title,
-                    &session.provider,
+                    &provider.resume_command(
+                        &session.uuid,
+                        session.file_path.as_deref(),
+                        session.home.as_deref(),
+                    ),
                     cmds,
                 )?;
                 actions::open_config(&session_config)?;
@@ -655,6 +1084,26 @@ impl App {
     }
 }
 
+/// The last line of a branch: its last session (or the end of that session's
+/// chain, if expanded), or the branch itself if collapsed or empty
+fn last_item(branch: &BranchNode, branch_data: &BranchData) -> SelectedItem {
+    match branch_data.sessions.last() {
+        Some(session) if branch.expanded => {
+            session_item(branch, session, branch_data.sessions.len() - 1)
+        }
+        _ => SelectedItem::Branch,
+    }
+}
+
+/// The last line of the session at `idx`: the end of its chain if expanded
+fn session_item(branch: &BranchNode, session: &SessionData, idx: usize) -> SelectedItem {
+    if !session.lineage.is_empty() && branch.expanded_chains.contains(&session.uuid) {
+        SelectedItem::Lineage(idx, session.lineage.len() - 1)
+    } else {
+        SelectedItem::Session(idx)
+    }
+}
+
 fn truncate(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
         s.to_string()
@@ -0,0 +1,220 @@
+use crate::db::{BranchData, NO_BRANCH, RepoData, SessionData};
+use crate::query::Query;
+use crate::tui::fuzzy::{FuzzyMatch, fuzzy_match};
+use std::cmp::Reverse;
+use std::path::Path;
+
+/// A repo left by the filter, with the char positions to highlight.
+/// `branches` lines up with `data.branches`.
+pub struct FilteredRepo {
+    pub data: RepoData,
+    pub name_matches: Vec<usize>,
+    pub branches: Vec<FilteredBranch>,
+}
+
+/// `session_matches` lines up with the branch's sessions and indexes into
+/// the title shown for each (its summary, or else its first prompt)
+pub struct FilteredBranch {
+    pub name_matches: Vec<usize>,
+    pub session_matches: Vec<Vec<usize>>,
+}
+
+impl FilteredRepo {
+    /// A repo shown as is, with nothing highlighted
+    pub fn unmatched(data: RepoData) -> Self {
+        let branches = data
+            .branches
+            .iter()
+            .map(|b| FilteredBranch {
+                name_matches: Vec::new(),
+                session_matches: vec![Vec::new(); b.sessions.len()],
+            })
+            .collect();
+        FilteredRepo {
+            data,
+            name_matches: Vec::new(),
+            branches,
+        }
+    }
+}
+
+/// Apply the in-memory part of `query` to a tree already narrowed down by
+/// its SQL terms.
+///
+/// Without a filter, shows branches with sessions modified since
+/// `recent_since` (ms since epoch; `None` shows everything). `dirty` terms
+/// keep branches checked out in a worktree that `is_dirty` (or not, negated).
+/// The bare words are fuzzy-matched:
+/// - a branch whose name matches is shown with all its sessions
+/// - otherwise only its sessions whose summary or first prompt matches
+/// - a repo whose name matches also shows its other recent branches
+///
+/// Repos, branches and sessions are ordered by their best score, each level
+/// keeping its usual order (name, recency) among equal scores.
+pub fn filter_tree(
+    repos: &[RepoData],
+    query: &Query,
+    recent_since: Option<i64>,
+    is_dirty: impl Fn(&Path) -> bool,
+) -> Vec<FilteredRepo> {
+    let is_recent = |branch: &BranchData| {
+        recent_since
+            .is_none_or(|since| branch.sessions.first().is_some_and(|s| s.modified >= since))
+    };
+
+    if query.is_empty() {
+        return repos
+            .iter()
+            .filter_map(|repo| {
+                let branches: Vec<BranchData> = repo
+                    .branches
+                    .iter()
+                    .filter(|b| is_recent(b))
+                    .cloned()
+                    .collect();
+                (!branches.is_empty())
+                    .then(|| FilteredRepo::unmatched(with_branches(repo, branches)))
+            })
+            .collect();
+    }
+
+    let filter = query.text();
+    let filter = filter.as_str();
+    let fuzzy = !filter.trim().is_empty();
+    let dirty_terms: Vec<bool> = query.dirty().collect();
+
+    let mut result = Vec::new();
+    for repo in repos {
+        let repo_match = fuzzy_match(filter, &repo.name);
+
+        let mut branches = Vec::new();
+        for branch in &repo.branches {
+            if !dirty_terms.is_empty() {
+                let dirty = repo.worktrees.iter().any(|wt| {
+                    wt.checked_out_branch.as_ref() == Some(&branch.branch) && is_dirty(&wt.path)
+                });
+                if dirty_terms.iter().any(|&wanted| wanted != dirty) {
+                    continue;
+                }
+            }
+
+            let branch_match = if branch.branch == NO_BRANCH {
+                None
+            } else {
+                fuzzy_match(filter, &branch.branch)
+            };
+
+            let mut sessions: Vec<(Option<i64>, &SessionData, Vec<usize>)> = branch
+                .sessions
+                .iter()
+                .map(|session| {
+                    let (score, positions) = match_session(filter, session);
+                    (score, session, positions)
+                })
+                .collect();
+            if fuzzy && branch_match.is_none() && !(repo_match.is_some() && is_recent(branch)) {
+                sessions.retain(|(score, _, _)| score.is_some());
+                if sessions.is_empty() {
+                    continue;
+                }
+            }
+            // Sessions already come newest first, and the sort is stable
+            sessions.sort_by_key(|(score, _, _)| Reverse(*score));
+
+            let score = sessions
+                .iter()
+                .filter_map(|(score, _, _)| *score)
+                .chain(branch_match.as_ref().map(|m| m.score))
+                .max();
+            let (sessions, session_matches) = sessions
+                .into_iter()
+                .map(|(_, session, positions)| (session.clone(), positions))
+                .unzip();
+            branches.push((
+                score,
+                BranchData {
+                    branch: branch.branch.clone(),
+                    sessions,
+                },
+                FilteredBranch {
+                    name_matches: positions(branch_match),
+                    session_matches,
+                },
+            ));
+        }
+        if branches.is_empty() {
+            continue;
+        }
+        branches.sort_by_key(|(score, _, _)| Reverse(*score));
+
+        let score = branches
+            .iter()
+            .filter_map(|(score, _, _)| *score)
+            .chain(repo_match.as_ref().map(|m| m.score))
+            .max();
+        let (branch_data, filtered): (Vec<_>, Vec<_>) = branches
+            .into_iter()
+            .map(|(_, data, filtered)| (data, filtered))
+            .unzip();
+        result.push((
+            score,
+            FilteredRepo {
+                data: with_branches(repo, branch_data),
+                name_matches: positions(repo_match),
+                branches: filtered,
+            },
+        ));
+    }
+
+    result.sort_by_key(|(score, _)| Reverse(*score));
+    result.into_iter().map(|(_, repo)| repo).collect()
+}
+
+/// Best score over a session's summary and first prompt, and those of the
+/// rest of its chain, with the positions to highlight if the matched text is
+/// the one the tree shows
+fn match_session(filter: &str, session: &SessionData) -> (Option<i64>, Vec<usize>) {
+    let summary = session
+        .summary
+        .as_deref()
+        .and_then(|s| fuzzy_match(filter, s));
+    let prompt = session
+        .first_prompt
+        .as_deref()
+        .and_then(|p| fuzzy_match(filter, p));
+    let lineage = session
+        .lineage
+        .iter()
+        .flat_map(|s| [s.summary.as_deref(), s.first_prompt.as_deref()])
+        .flatten()
+        .filter_map(|text| fuzzy_match(filter, text))
+        .map(|m| m.score)
+        .max();
+
+    let score = summary
+        .iter()
+        .chain(&prompt)
+        .map(|m| m.score)
+        .chain(lineage)
+        .max();
+    let shown = if session.summary.is_some() {
+        summary
+    } else {
+        prompt
+    };
+    (score, positions(shown))
+}
+
+fn positions(m: Option<FuzzyMatch>) -> Vec<usize> {
+    m.map(|m| m.positions).unwrap_or_default()
+}
+
+fn with_branches(repo: &RepoData, branches: Vec<BranchData>) -> RepoData {
+    RepoData {
+        path: repo.path.clone(),
+        name: repo.name.clone(),
+        worktrees: repo.worktrees.clone(),
+        branches,
+        missing_since: repo.missing_since,
+    }
+}
@@ -0,0 +1,178 @@
+/// A fuzzy subsequence match: its score (higher is better) and the char
+/// indices of the matched characters in the text, for highlighting
+#[derive(Debug, Clone, Default)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8; // match at the start of a word
+const BONUS_CONSECUTIVE: i64 = 4; // match right after the previous one
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+const MAX_GAP_PENALTY: i64 = 12;
+/// Lowest score per pattern char a match may average. Matched chars earn
+/// SCORE_MATCH and lose it to gaps, so a pattern scattered across a long
+/// prompt falls short unless its chars start words or run together.
+const MIN_SCORE_PER_CHAR: i64 = SCORE_MATCH;
+
+/// Match `pattern` as a subsequence of `text`, e.g. `authrf` in `feature/auth-refresh`.
+/// Whitespace in the pattern is ignored. Matching is case-insensitive unless
+/// the pattern contains an uppercase letter. Matches scoring below
+/// `MIN_SCORE_PER_CHAR` per pattern char are too scattered to count.
+///
+/// Runs in linear time: a forward pass finds where the pattern first
+/// completes, a backward pass from there finds the tightest window, and the
+/// match is taken greedily inside that window (the same approach as fzf's
+/// fast path), so long prompts stay cheap to match on every keystroke.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let case_sensitive = pattern.chars().any(|c| c.is_uppercase());
+    let pattern: Vec<char> = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch::default());
+    }
+    // The pattern has no uppercase unless case-sensitive, so only the text needs folding
+    let eq = |p: char, t: char| {
+        p == t
+            || (!case_sensitive
+                && if t.is_ascii() {
+                    t.to_ascii_lowercase() == p
+                } else {
+                    t.to_lowercase().eq(std::iter::once(p))
+                })
+    };
+
+    let mut pi = 0;
+    let mut end = None;
+    for (ci, c) in text.chars().enumerate() {
+        if eq(pattern[pi], c) {
+            pi += 1;
+            if pi == pattern.len() {
+                end = Some(ci);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let chars: Vec<char> = text.chars().take(end + 1).collect();
+    let mut start = 0;
+    let mut pi = pattern.len();
+    for ci in (0..=end).rev() {
+        if eq(pattern[pi - 1], chars[ci]) {
+            pi -= 1;
+            if pi == 0 {
+                start = ci;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    for (ci, &c) in chars.iter().enumerate().skip(start) {
+        if positions.len() < pattern.len() && eq(pattern[positions.len()], c) {
+            positions.push(ci);
+        }
+    }
+
+    let score = score(&chars, &positions);
+    if score < MIN_SCORE_PER_CHAR * pattern.len() as i64 {
+        return None;
+    }
+    Some(FuzzyMatch { score, positions })
+}
+
+fn score(chars: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+    for &pos in positions {
+        score += SCORE_MATCH;
+        if is_word_start(chars, pos) {
+            score += BONUS_BOUNDARY;
+        }
+        match prev {
+            Some(p) if p + 1 == pos => score += BONUS_CONSECUTIVE,
+            Some(p) => {
+                let gap = (pos - p - 1) as i64;
+                score -=
+                    (PENALTY_GAP_START + (gap - 1) * PENALTY_GAP_EXTENSION).min(MAX_GAP_PENALTY);
+            }
+            None => {}
+        }
+        prev = Some(pos);
+    }
+    score
+}
+
+/// Start of the text, after a separator (`/`, `-`, `_`, space, ...) or a camelCase hump
+fn is_word_start(chars: &[char], pos: usize) -> bool {
+    let Some(&prev) = pos.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return true;
+    };
+    !prev.is_alphanumeric() || (prev.is_lowercase() && chars[pos].is_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_of(pattern: &str, text: &str) -> Option<i64> {
+        fuzzy_match(pattern, text).map(|m| m.score)
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        let m = fuzzy_match(" ", "anything").unwrap();
+        assert_eq!(m.score, 0);
+        assert!(m.positions.is_empty());
+    }
+
+    #[test]
+    fn matches_subsequence_at_word_starts() {
+        let m = fuzzy_match("authrf", "feature/auth-refresh").unwrap();
+        assert_eq!(m.positions, vec![8, 9, 10, 11, 13, 15]);
+    }
+
+    #[test]
+    fn missing_char_does_not_match() {
+        assert!(fuzzy_match("authz", "feature/auth-refresh").is_none());
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(fuzzy_match("api", "API gateway").is_some());
+        assert!(fuzzy_match("API", "api gateway").is_none());
+        assert!(fuzzy_match("Api", "Api gateway").is_some());
+    }
+
+    #[test]
+    fn takes_the_tightest_window() {
+        // The first `f` starts a window the later `fix` is tighter than
+        let m = fuzzy_match("fix", "f and then fix it").unwrap();
+        assert_eq!(m.positions, vec![11, 12, 13]);
+    }
+
+    #[test]
+    fn consecutive_and_boundary_matches_score_higher() {
+        let consecutive = score_of("log", "login page").unwrap();
+        let boundaries = score_of("lpg", "login page").unwrap();
+        let gapped = score_of("lgn", "login page").unwrap();
+        assert!(consecutive > boundaries, "{} {}", consecutive, boundaries);
+        assert!(boundaries > gapped, "{} {}", boundaries, gapped);
+    }
+
+    #[test]
+    fn camel_case_humps_are_word_starts() {
+        assert!(is_word_start(&['f', 'o', 'o', 'B', 'a', 'r'], 3));
+        assert!(!is_word_start(&['f', 'o', 'o', 'b', 'a', 'r'], 3));
+    }
+
+    #[test]
+    fn scattered_short_pattern_does_not_match_long_text() {
+        let prompt = "please look at why the build is failing on the release branch";
+        assert!(fuzzy_match("pk", prompt).is_none());
+        assert!(fuzzy_match("wbf", prompt).is_some()); // why build failing
+        assert!(fuzzy_match("build", prompt).is_some());
+    }
+}
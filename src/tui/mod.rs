@@ -1,9 +1,15 @@
 mod app;
 mod confirmation;
+mod filter;
+mod fuzzy;
+mod preview;
+mod transcript_cache;
 mod tree;
+mod worktree_cache;
 
 use crate::config::Config;
 use crate::db::Database;
+use crate::scanner::ScanEvent;
 use app::App;
 use crossterm::{
     event::{
@@ -16,8 +22,18 @@ use crossterm::{
 use ratatui::{prelude::*, widgets::*};
 use std::error::Error;
 use std::io;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 
-pub fn run(db: Database, config: Config, filter: String) -> Result<(), Box<dyn Error>> {
+/// How often the UI checks for background results while idle
+const TICK_RATE: Duration = Duration::from_millis(100);
+
+pub fn run(
+    db: Database,
+    config: Config,
+    filter: String,
+    scan_events: Option<Receiver<ScanEvent>>,
+) -> Result<(), Box<dyn Error>> {
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -26,84 +42,127 @@ pub fn run(db: Database, config: Config, filter: String) -> Result<(), Box<dyn E
     let mut terminal = Terminal::new(backend)?;
 
     // Create app state
-    let mut app = App::new(db, config, filter)?;
+    let mut app = App::new(db, config, filter, scan_events)?;
 
     // Main loop
+    let mut needs_draw = true;
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        if needs_draw {
+            terminal.draw(|f| ui(f, &mut app))?;
+        }
+        needs_draw = true;
+
+        // Wake up periodically so background results get drawn without input
+        if !event::poll(TICK_RATE)? {
+            needs_draw = app.poll_background();
+            continue;
+        }
 
         match event::read()? {
-            Event::Key(key) => {
-                if key.kind == KeyEventKind::Press {
-                    // Handle Ctrl+C to quit
-                    if key.code == KeyCode::Char('c')
-                        && key.modifiers.contains(KeyModifiers::CONTROL)
-                    {
-                        break;
-                    }
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                // Handle Ctrl+C to quit
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    break;
+                }
 
-                    // Handle scroll keys: Option+Up/Down and PageUp/PageDown
-                    if key.modifiers.contains(KeyModifiers::ALT) {
-                        match key.code {
-                            KeyCode::Up => {
-                                app.scroll_up(1);
-                                continue;
-                            }
-                            KeyCode::Down => {
-                                app.scroll_down(1);
-                                continue;
-                            }
-                            _ => {}
-                        }
-                    }
+                // Ctrl+R cycles the recent window
+                if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.cycle_recent_window();
+                    continue;
+                }
+
+                // Ctrl+A toggles archived repos and sessions
+                if key.code == KeyCode::Char('a') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.toggle_archived();
+                    continue;
+                }
 
+                // Ctrl+P toggles the transcript preview, Ctrl+U/Ctrl+D scroll it
+                if key.modifiers.contains(KeyModifiers::CONTROL) {
+                    let half_page = (app.preview_area.height / 2).max(1) as i32;
                     match key.code {
-                        KeyCode::PageUp => {
-                            app.scroll_up(app.viewport_height / 2);
+                        KeyCode::Char('p') => {
+                            app.toggle_preview();
+                            continue;
+                        }
+                        KeyCode::Char('u') if app.show_preview => {
+                            app.scroll_preview(half_page);
                             continue;
                         }
-                        KeyCode::PageDown => {
-                            app.scroll_down(app.viewport_height / 2);
+                        KeyCode::Char('d') if app.show_preview => {
+                            app.scroll_preview(-half_page);
                             continue;
                         }
-                        KeyCode::Home => {
-                            app.scroll_to_top();
+                        _ => {}
+                    }
+                }
+
+                // Handle scroll keys: Option+Up/Down and PageUp/PageDown
+                if key.modifiers.contains(KeyModifiers::ALT) {
+                    match key.code {
+                        KeyCode::Up => {
+                            app.scroll_up(1);
                             continue;
                         }
-                        KeyCode::End => {
-                            app.scroll_to_bottom();
+                        KeyCode::Down => {
+                            app.scroll_down(1);
                             continue;
                         }
                         _ => {}
                     }
+                }
 
-                    match app.handle_key(key.code) {
-                        app::Action::Continue => {}
-                        app::Action::Launch => {
-                            // Restore terminal before launching
-                            disable_raw_mode()?;
-                            execute!(
-                                terminal.backend_mut(),
-                                LeaveAlternateScreen,
-                                DisableMouseCapture
-                            )?;
-                            terminal.show_cursor()?;
-
-                            app.launch_selection()?;
-                            return Ok(());
-                        }
+                match key.code {
+                    KeyCode::PageUp => {
+                        app.scroll_up(app.viewport_height / 2);
+                        continue;
                     }
+                    KeyCode::PageDown => {
+                        app.scroll_down(app.viewport_height / 2);
+                        continue;
+                    }
+                    KeyCode::Home => {
+                        app.scroll_to_top();
+                        continue;
+                    }
+                    KeyCode::End => {
+                        app.scroll_to_bottom();
+                        continue;
+                    }
+                    _ => {}
                 }
-            }
-            Event::Mouse(MouseEvent { kind, .. }) => match kind {
-                MouseEventKind::ScrollUp => {
-                    app.scroll_up(1);
+
+                match app.handle_key(key.code) {
+                    app::Action::Continue => {}
+                    app::Action::Launch => {
+                        // Restore terminal before launching
+                        disable_raw_mode()?;
+                        execute!(
+                            terminal.backend_mut(),
+                            LeaveAlternateScreen,
+                            DisableMouseCapture
+                        )?;
+                        terminal.show_cursor()?;
+
+                        app.launch_selection()?;
+                        return Ok(());
+                    }
                 }
-                MouseEventKind::ScrollDown => {
-                    app.scroll_down(1);
+            }
+            // The wheel scrolls whichever pane the pointer is over
+            Event::Mouse(MouseEvent {
+                kind, column, row, ..
+            }) => {
+                let over_preview =
+                    app.show_preview && app.preview_area.contains(Position::new(column, row));
+                match kind {
+                    MouseEventKind::ScrollUp if over_preview => app.scroll_preview(1),
+                    MouseEventKind::ScrollDown if over_preview => app.scroll_preview(-1),
+                    MouseEventKind::ScrollUp => app.scroll_up(1),
+                    MouseEventKind::ScrollDown => app.scroll_down(1),
+                    _ => {}
                 }
-                _ => {}
-            },
+            }
             _ => {}
         }
     }
@@ -130,20 +189,61 @@ fn ui(f: &mut Frame, app: &mut App) {
         ])
         .split(f.area());
 
-    // Filter input
-    let filter_block = Block::default()
+    // Filter input, with background scan progress in the top-right corner
+    let mut filter_block = Block::default()
         .borders(Borders::ALL)
         .title(" ws - worktree manager ");
+    if app.show_archived {
+        filter_block = filter_block.title(Line::styled(
+            " +archived ",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if let Some(ref status) = app.scan_status {
+        filter_block = filter_block.title(scan_status_line(status).right_aligned());
+    }
 
-    let filter_text = Paragraph::new(format!("Filter: {}", app.filter))
+    // A filter that doesn't parse keeps the last valid tree and explains why
+    let mut filter_spans = vec![Span::raw(format!("Filter: {}", app.filter))];
+    if let Some(error) = &app.filter_error {
+        filter_spans.push(Span::styled(
+            format!("  ✗ {}", error),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    let filter_text = Paragraph::new(Line::from(filter_spans))
         .block(filter_block)
         .style(Style::default());
     f.render_widget(filter_text, chunks[0]);
 
-    // Tree view
-    let tree_block = Block::default().borders(Borders::ALL);
-    let inner_area = tree_block.inner(chunks[1]);
-    f.render_widget(tree_block, chunks[1]);
+    // Tree view, with the transcript preview to its right when toggled on
+    let tree_area = if app.show_preview {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+        app.preview_area = panes[1];
+        app.preview_max_scroll = preview::render_preview(f, panes[1], app);
+        panes[0]
+    } else {
+        chunks[1]
+    };
+
+    // The recent window only applies while the filter is empty
+    let mut tree_block = Block::default().borders(Borders::ALL);
+    if app.text_query().is_some() {
+        tree_block = tree_block.title(Line::styled(
+            " text search ",
+            Style::default().fg(Color::DarkGray),
+        ));
+    } else if app.filter.is_empty() {
+        tree_block = tree_block.title(Line::styled(
+            format!(" recent: {} ", app.recent_window.label()),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    let inner_area = tree_block.inner(tree_area);
+    f.render_widget(tree_block, tree_area);
 
     // Update viewport height for scroll calculations
     app.viewport_height = inner_area.height;
@@ -154,7 +254,7 @@ fn ui(f: &mut Frame, app: &mut App) {
     let help_text = if app.confirm_dialog.is_some() {
         " y/n confirm  Esc cancel "
     } else {
-        " ↑↓ navigate  ←→ switch worktree  Space select  Enter launch  PgUp/PgDn scroll  Ctrl+C quit "
+        " ↑↓ navigate  ←→ worktree  Space select  Enter launch  Tab chain  PgUp/PgDn scroll  Ctrl+P preview  Ctrl+R recent  Ctrl+A archived  Ctrl+C quit "
     };
     let help = Paragraph::new(help_text).style(Style::default().fg(Color::DarkGray));
     f.render_widget(help, chunks[2]);
@@ -164,3 +264,47 @@ fn ui(f: &mut Frame, app: &mut App) {
         confirmation::render_dialog(f, dialog);
     }
 }
+
+fn scan_status_line(status: &ScanEvent) -> Line<'static> {
+    match status {
+        ScanEvent::Progress {
+            repos_done,
+            repos_total,
+            sessions_done,
+            sessions_total,
+        } => Line::styled(
+            format!(
+                " scanning: {}/{} repos, {}/{} sessions ",
+                repos_done, repos_total, sessions_done, sessions_total
+            ),
+            Style::default().fg(Color::Yellow),
+        ),
+        ScanEvent::Finished(Ok(stats)) if !stats.warnings.is_empty() => Line::styled(
+            format!(
+                " scanned {} repos, {} sessions with {} warning{} (see ws --scan) ",
+                stats.repo_count,
+                stats.session_count,
+                stats.warnings.len(),
+                if stats.warnings.len() == 1 { "" } else { "s" }
+            ),
+            Style::default().fg(Color::Yellow),
+        ),
+        ScanEvent::Finished(Ok(stats)) => Line::styled(
+            format!(
+                " scanned {} repos, {} sessions in {:.1}s ",
+                stats.repo_count,
+                stats.session_count,
+                stats.duration_ms as f64 / 1000.0
+            ),
+            Style::default().fg(Color::DarkGray),
+        ),
+        ScanEvent::Skipped => Line::styled(
+            " another scan is running ",
+            Style::default().fg(Color::DarkGray),
+        ),
+        ScanEvent::Finished(Err(e)) => Line::styled(
+            format!(" scan failed: {} ", e),
+            Style::default().fg(Color::Red),
+        ),
+    }
+}
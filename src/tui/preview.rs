@@ -0,0 +1,189 @@
+use crate::scanner::claude::Role;
+use crate::tui::app::App;
+use ratatui::{prelude::*, widgets::*};
+
+/// Render the selected session's recent turns, newest at the bottom.
+/// Returns how many lines the transcript can scroll up, given the height left
+/// to it under the edited files.
+pub fn render_preview(f: &mut Frame, area: Rect, app: &App) -> u16 {
+    let scroll = app.preview_scroll();
+    let mut block = Block::default().borders(Borders::ALL).title(" preview ");
+    if scroll > 0 {
+        block = block.title(
+            Line::styled(
+                format!(" ↑{} ", scroll),
+                Style::default().fg(Color::DarkGray),
+            )
+            .right_aligned(),
+        );
+    }
+    let mut inner = block.inner(area);
+    f.render_widget(block, area);
+
+    // Files written take up to a third of the pane, above the transcript
+    let files = app.preview_files();
+    if !files.is_empty() {
+        let height = (files.len() as u16 + 2).min(inner.height / 3);
+        let [files_area, rest] =
+            Layout::vertical([Constraint::Length(height), Constraint::Min(0)]).areas(inner);
+        let project = app.selected_session().map(|s| s.project_path.as_str());
+        let lines = file_lines(files, project, files_area.height, inner.width as usize);
+        f.render_widget(Paragraph::new(lines), files_area);
+        inner = rest;
+    }
+
+    let lines = match preview_lines(app, inner.width as usize) {
+        Ok(lines) => lines,
+        Err(message) => {
+            let message = Paragraph::new(message)
+                .style(Style::default().fg(Color::DarkGray))
+                .wrap(Wrap { trim: false });
+            f.render_widget(message, inner);
+            return 0;
+        }
+    };
+
+    // Anchor the end of the transcript to the bottom of the pane
+    let total = lines.len();
+    let top = total.saturating_sub(inner.height as usize + scroll as usize);
+    let paragraph = Paragraph::new(lines).scroll((top as u16, 0));
+    f.render_widget(paragraph, inner);
+    total.saturating_sub(inner.height as usize) as u16
+}
+
+/// The transcript as wrapped lines, or why there is nothing to show
+fn preview_lines(app: &App, width: usize) -> Result<Vec<Line<'static>>, String> {
+    let session = app
+        .selected_session()
+        .ok_or("Select a session to preview its transcript.")?;
+    session
+        .file_path
+        .as_ref()
+        .ok_or("No transcript recorded for this session yet; it is read on the next scan.")?;
+    let turns = match app.transcript_cache.get(&session.uuid) {
+        None => return Err("Loading…".to_string()),
+        Some(Err(e)) => return Err(format!("Could not read transcript: {}", e)),
+        Some(Ok(turns)) if turns.is_empty() => return Err("Empty transcript.".to_string()),
+        Some(Ok(turns)) => turns,
+    };
+
+    // Color the assistant like its sessions in the tree
+    let assistant = app.providers.display_name(&session.provider).to_string();
+    let assistant_color = app.providers.style(&session.provider).color;
+
+    let mut lines = Vec::new();
+    let mut speaker = None;
+    for turn in turns {
+        // Tool calls belong to the assistant's turn
+        let turn_speaker = if turn.role == Role::User {
+            Role::User
+        } else {
+            Role::Assistant
+        };
+        if speaker != Some(turn_speaker) {
+            if speaker.is_some() {
+                lines.push(Line::raw(""));
+            }
+            lines.push(match turn_speaker {
+                Role::User => Line::styled("You", Style::default().fg(Color::Cyan).bold()),
+                _ => Line::styled(
+                    assistant.clone(),
+                    Style::default().fg(assistant_color).bold(),
+                ),
+            });
+            speaker = Some(turn_speaker);
+        }
+
+        if turn.role == Role::Tool {
+            let text = format!("⚙ {}", turn.text.lines().next().unwrap_or_default());
+            lines.push(Line::styled(
+                truncate(&text, width),
+                Style::default().fg(Color::DarkGray),
+            ));
+        } else {
+            lines.extend(wrap(&turn.text, width).into_iter().map(Line::raw));
+        }
+    }
+    Ok(lines)
+}
+
+/// A header and one line per file, relative to the session's directory when
+/// inside it, followed by a blank line. Files that don't fit are counted instead.
+fn file_lines(
+    files: &[String],
+    project: Option<&str>,
+    height: u16,
+    width: usize,
+) -> Vec<Line<'static>> {
+    let noun = if files.len() == 1 { "file" } else { "files" };
+    let mut lines = vec![Line::styled(
+        format!("Edited {} {}", files.len(), noun),
+        Style::default().fg(Color::Green).bold(),
+    )];
+
+    let room = (height as usize).saturating_sub(2);
+    let shown = if files.len() > room {
+        room.saturating_sub(1)
+    } else {
+        files.len()
+    };
+    for file in &files[..shown] {
+        let relative = project
+            .and_then(|p| file.strip_prefix(p))
+            .and_then(|f| f.strip_prefix('/'))
+            .unwrap_or(file);
+        lines.push(Line::styled(
+            truncate(&format!("  {}", relative), width),
+            Style::default().fg(Color::Green),
+        ));
+    }
+    if shown < files.len() {
+        lines.push(Line::styled(
+            format!("  … and {} more", files.len() - shown),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    lines.push(Line::raw(""));
+    lines
+}
+
+/// Word-wrap `text` to `width` chars, breaking words longer than a line
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.trim_end().lines() {
+        let mut line = String::new();
+        let mut len = 0;
+        for word in paragraph.split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            if len > 0 && len + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+                len = 0;
+            }
+            if len > 0 {
+                line.push(' ');
+                len += 1;
+            }
+            while len + word.len() > width {
+                let rest = word.split_off(width - len);
+                line.extend(word);
+                lines.push(std::mem::take(&mut line));
+                len = 0;
+                word = rest;
+            }
+            len += word.len();
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let kept: String = text.chars().take(width.saturating_sub(1)).collect();
+        format!("{}…", kept)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::scanner::Provider;
+use crate::scanner::claude::Turn;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Turns kept per session; the preview shows the end of the transcript
+const MAX_TURNS: usize = 200;
+
+/// How long a transcript is trusted before it is re-read, for sessions still running
+const MAX_AGE: Duration = Duration::from_secs(10);
+
+/// Transcripts kept in memory; the least recently read are dropped first
+const MAX_ENTRIES: usize = 32;
+
+pub type Transcript = Result<Vec<Turn>, String>;
+
+struct Entry {
+    transcript: Transcript,
+    fetched_at: Instant,
+}
+
+/// Recent turns of session transcripts keyed by session uuid.
+///
+/// Transcripts are parsed on a background thread, and only for the session
+/// being previewed, so navigating the tree never waits on reading JSONL.
+pub struct TranscriptCache {
+    entries: HashMap<String, Entry>,
+    pending: HashSet<String>,
+    requests: Sender<Request>,
+    results: Receiver<(String, Transcript)>,
+}
+
+struct Request {
+    uuid: String,
+    path: PathBuf,
+    provider: Arc<dyn Provider>,
+}
+
+impl TranscriptCache {
+    pub fn new() -> Self {
+        let (requests, request_rx) = mpsc::channel::<Request>();
+        let (result_tx, results) = mpsc::channel();
+
+        // Exits once the cache (and with it the request sender) is dropped
+        thread::spawn(move || {
+            for request in request_rx {
+                let turns = request
+                    .provider
+                    .read_turns(&request.path, &request.uuid, MAX_TURNS);
+                if result_tx
+                    .send((request.uuid, turns.map_err(|e| e.to_string())))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        TranscriptCache {
+            entries: HashMap::new(),
+            pending: HashSet::new(),
+            requests,
+            results,
+        }
+    }
+
+    /// Last parsed transcript, if one has been read yet
+    pub fn get(&self, uuid: &str) -> Option<&Transcript> {
+        self.entries.get(uuid).map(|e| &e.transcript)
+    }
+
+    /// Queue a background read of session `uuid` from `path` by `provider`,
+    /// if its transcript is missing or stale
+    pub fn request(&mut self, uuid: &str, path: &Path, provider: Arc<dyn Provider>) {
+        if self.pending.contains(uuid) {
+            return;
+        }
+        let fresh = self
+            .entries
+            .get(uuid)
+            .is_some_and(|e| e.fetched_at.elapsed() < MAX_AGE);
+        let request = Request {
+            uuid: uuid.to_string(),
+            path: path.to_path_buf(),
+            provider,
+        };
+        if !fresh && self.requests.send(request).is_ok() {
+            self.pending.insert(uuid.to_string());
+        }
+    }
+
+    /// Collect finished background reads. Returns true if anything changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((uuid, transcript)) = self.results.try_recv() {
+            self.pending.remove(&uuid);
+            if self.entries.len() >= MAX_ENTRIES
+                && !self.entries.contains_key(&uuid)
+                && let Some(oldest) = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.fetched_at)
+                    .map(|(p, _)| p.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+            self.entries.insert(
+                uuid,
+                Entry {
+                    transcript,
+                    fetched_at: Instant::now(),
+                },
+            );
+            changed = true;
+        }
+        changed
+    }
+}
@@ -1,10 +1,23 @@
+use crate::config::{Config, SessionColumn};
+use crate::db::{MATCH_END, MATCH_START, SessionData};
+use crate::scanner::claude::LinkKind;
+use crate::scanner::git::WorktreeStatus;
+use crate::stats;
 use crate::tui::app::{App, SelectedItem};
 use ratatui::{prelude::*, widgets::*};
 
 pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
     if app.repos.is_empty() {
-        let empty = Paragraph::new("No repos found. Run 'ws --scan' first.")
-            .style(Style::default().fg(Color::DarkGray));
+        let message = if app.text_query().is_some_and(|q| q.is_empty()) {
+            "Type words to search session transcripts."
+        } else if app.filter_error.is_some() {
+            "Invalid filter."
+        } else if !app.filter.is_empty() {
+            "No matches."
+        } else {
+            "No repos found. Run 'ws --scan' first."
+        };
+        let empty = Paragraph::new(message).style(Style::default().fg(Color::DarkGray));
         f.render_widget(empty, area);
         return;
     }
@@ -23,23 +36,25 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
             .data
             .worktrees
             .iter()
-            .enumerate()
-            .map(|(wt_idx, wt)| {
-                let state = &repo.worktree_states[wt_idx];
-
-                // Color logic: red if dirty, yellow if WIP, white otherwise
-                let style = if state.is_dirty {
+            .map(|wt| {
+                let state = &app.worktree_status(&wt.path);
+
+                // Color logic: magenta if conflicted or mid-rebase/merge, red if dirty,
+                // yellow if WIP, white otherwise
+                let style = if state.is_conflicted() || state.operation.is_some() {
+                    Style::default().fg(Color::Magenta)
+                } else if state.is_dirty() {
                     Style::default().fg(Color::Red)
-                } else if state.has_wip {
+                } else if state.has_wip_commit() {
                     Style::default().fg(Color::Yellow)
                 } else {
                     Style::default().fg(Color::White)
                 };
 
-                // Show [name] for selected worktree when repo is selected,
+                // Show [name status] for each worktree when repo is selected,
                 // otherwise show dot (filled if has a branch checked out)
                 if repo_selected {
-                    Span::styled(format!("[{}] ", wt.name), style)
+                    Span::styled(format!("[{}] ", worktree_label(&wt.name, state)), style)
                 } else {
                     let symbol = if wt.checked_out_branch.is_some() {
                         "●"
@@ -51,15 +66,26 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
             })
             .collect();
 
-        let mut repo_spans = vec![Span::styled(
-            format!("{} {} ", expand_char, repo.data.name),
-            if repo_selected {
-                Style::default().bold().fg(Color::White)
-            } else {
-                Style::default().fg(Color::Cyan)
-            },
-        )];
+        let repo_style = if repo_selected {
+            Style::default().bold().fg(Color::White)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        let mut repo_spans = vec![Span::styled(format!("{} ", expand_char), repo_style)];
+        repo_spans.extend(highlight_spans(
+            &repo.data.name,
+            &repo.name_matches,
+            usize::MAX,
+            repo_style,
+        ));
+        repo_spans.push(Span::styled(" ", repo_style));
         repo_spans.extend(worktree_spans);
+        if repo.data.missing_since.is_some() {
+            repo_spans.push(Span::styled(
+                "(archived)",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
 
         let repo_line = Line::from(repo_spans);
         lines.push(if repo_selected {
@@ -86,21 +112,22 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
                     .enumerate()
                     .flat_map(|(wt_idx, wt)| {
                         let is_selected_wt = wt_idx == branch.selected_worktree_idx;
-                        let state = &repo.worktree_states[wt_idx];
+                        let state = &app.worktree_status(&wt.path);
 
                         // Color logic:
                         // - Green: this branch IS checked out in this worktree
+                        // - Magenta: worktree has conflicts or a rebase/merge in progress
                         // - Red: worktree is dirty
                         // - Yellow: worktree has WIP commit
                         // - White: otherwise
-                        let is_checked_out = wt
-                            .checked_out_branch
-                            .as_ref()
-                            .map_or(false, |b| b == &branch_data.branch);
+                        let is_checked_out =
+                            wt.checked_out_branch.as_ref() == Some(&branch_data.branch);
 
-                        let style = if state.is_dirty {
+                        let style = if state.is_conflicted() || state.operation.is_some() {
+                            Style::default().fg(Color::Magenta)
+                        } else if state.is_dirty() {
                             Style::default().fg(Color::Red)
-                        } else if state.has_wip {
+                        } else if state.has_wip_commit() {
                             Style::default().fg(Color::Yellow)
                         } else if is_checked_out {
                             Style::default().fg(Color::Green)
@@ -110,11 +137,14 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
 
                         let symbol = if is_selected_wt { "●" } else { "○" };
 
-                        // Show dot, then [name] for selected worktree when branch is selected
+                        // Show dot, then [name status] for selected worktree when branch is selected
                         if is_selected_wt && is_selected_branch {
                             vec![
-                                Span::styled(format!("{}", symbol), style),
-                                Span::styled(format!("[{}] ", wt.name), style),
+                                Span::styled(symbol.to_string(), style),
+                                Span::styled(
+                                    format!("[{}] ", worktree_label(&wt.name, state)),
+                                    style,
+                                ),
                             ]
                         } else {
                             vec![Span::styled(format!("{} ", symbol), style)]
@@ -122,17 +152,22 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
                     })
                     .collect();
 
+                let branch_style = if branch_selected {
+                    Style::default().bold().fg(Color::White)
+                } else {
+                    Style::default()
+                };
                 let mut branch_spans = vec![
                     Span::raw("    "),
-                    Span::styled(
-                        format!("{} {} ", expand_char, branch_data.branch),
-                        if branch_selected {
-                            Style::default().bold().fg(Color::White)
-                        } else {
-                            Style::default()
-                        },
-                    ),
+                    Span::styled(format!("{} ", expand_char), branch_style),
                 ];
+                branch_spans.extend(highlight_spans(
+                    &branch_data.branch,
+                    &branch.name_matches,
+                    usize::MAX,
+                    branch_style,
+                ));
+                branch_spans.push(Span::styled(" ", branch_style));
                 branch_spans.extend(worktree_spans);
 
                 let branch_line = Line::from(branch_spans);
@@ -142,87 +177,297 @@ pub fn render_tree(f: &mut Frame, area: Rect, app: &App) {
                     branch_line
                 });
 
-                // Sessions (if expanded)
+                // Sessions (if expanded); a chain of sessions shows its newest
+                // leaf, and the rest of the chain below it once expanded
                 if branch.expanded {
                     for (session_idx, session) in branch_data.sessions.iter().enumerate() {
-                        let session_selected = is_selected_branch
-                            && app.selected_item == SelectedItem::Session(session_idx);
-                        let is_checked = branch.selected_sessions.contains(&session.uuid);
+                        let chain_expanded = branch.expanded_chains.contains(&session.uuid);
+                        let marker = match (session.lineage.is_empty(), chain_expanded) {
+                            (true, _) => "  ",
+                            (false, true) => "▾ ",
+                            (false, false) => "▸ ",
+                        };
+                        let matches = branch
+                            .session_matches
+                            .get(session_idx)
+                            .map_or(&[][..], Vec::as_slice);
+                        lines.push(session_line(
+                            app,
+                            session,
+                            SessionRow {
+                                indent: format!("      {}", marker),
+                                selected: is_selected_branch
+                                    && app.selected_item == SelectedItem::Session(session_idx),
+                                checked: branch.selected_sessions.contains(&session.uuid),
+                                matches,
+                                width: area.width as usize,
+                            },
+                        ));
+
+                        if chain_expanded {
+                            for (member_idx, member) in session.lineage.iter().enumerate() {
+                                lines.push(session_line(
+                                    app,
+                                    member,
+                                    SessionRow {
+                                        indent: "          ↳ ".to_string(),
+                                        selected: is_selected_branch
+                                            && app.selected_item
+                                                == SelectedItem::Lineage(session_idx, member_idx),
+                                        checked: branch.selected_sessions.contains(&member.uuid),
+                                        matches: &[],
+                                        width: area.width as usize,
+                                    },
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
 
-                        let checkbox = if is_checked { "[x]" } else { "[ ]" };
+    let paragraph = Paragraph::new(lines).scroll((app.scroll_offset, 0));
+    f.render_widget(paragraph, area);
+}
 
-                        // Format metadata first so we know its width
-                        let msg_count = session
-                            .message_count
-                            .map(|c| format!("{} msg", c))
-                            .unwrap_or_default();
-                        let relative_time = format_relative_time(session.modified);
+/// How a session line is placed and marked in the tree
+struct SessionRow<'a> {
+    indent: String,
+    selected: bool,
+    checked: bool,
+    matches: &'a [usize], // Chars of the title matched by the filter
+    width: usize,
+}
 
-                        let metadata = if msg_count.is_empty() {
-                            relative_time
-                        } else {
-                            format!("{} • {}", msg_count, relative_time)
-                        };
+fn session_line(app: &App, session: &SessionData, row: SessionRow) -> Line<'static> {
+    let checkbox = if row.checked { "[x]" } else { "[ ]" };
+
+    // Format metadata first so we know its width
+    let mut metadata = session
+        .message_count
+        .map(|c| format!("{} msg", c))
+        .unwrap_or_default();
+    let mut columns = usage_columns(session, &app.config);
+    if !session.lineage.is_empty() {
+        columns.insert(0, format!("{} sessions", session.lineage.len() + 1));
+    }
+    if let Some(link) = session.link.filter(|_| session.lineage.is_empty()) {
+        columns.insert(0, link_label(link).to_string());
+    }
+    columns.push(format_relative_time(session.modified));
+    for column in columns {
+        if !metadata.is_empty() {
+            metadata.push_str(" • ");
+        }
+        metadata.push_str(&column);
+    }
+    let is_archived = session.missing_since.is_some();
+    if is_archived {
+        metadata.push_str(" • archived");
+    }
 
-                        // Compute summary max width dynamically from terminal width
-                        // Layout: "        [x] " (13) + summary + " • " (3) + metadata
-                        let fixed_width = 13 + 3 + metadata.len();
-                        let summary_max = (area.width as usize).saturating_sub(fixed_width).max(10);
-
-                        let summary = session
-                            .summary
-                            .as_ref()
-                            .or(session.first_prompt.as_ref())
-                            .map(|s| truncate_str(s, summary_max))
-                            .unwrap_or_else(|| "No summary".to_string());
-
-                        // Color by provider: orange for Claude, white/gray for Codex
-                        let is_claude = session.provider.as_str() != "codex";
-                        let summary_style = if session_selected {
-                            Style::default().fg(Color::Cyan)
-                        } else if is_claude {
-                            Style::default().fg(Color::Rgb(255, 165, 0)) // Orange for Claude
-                        } else {
-                            Style::default().fg(Color::Gray) // Gray for Codex
-                        };
-                        let metadata_style = if session_selected {
-                            Style::default().fg(Color::Gray)
-                        } else if is_claude {
-                            Style::default().fg(Color::Rgb(200, 130, 0)) // Darker orange for Claude
-                        } else {
-                            Style::default().fg(Color::DarkGray)
-                        };
+    // Compute summary max width dynamically from terminal width
+    // Layout: indent + "[x] " (4) + summary + " • " (3) + metadata
+    let fixed_width = row.indent.chars().count() + 4 + 3 + metadata.chars().count() + 1;
+    let summary_max = row.width.saturating_sub(fixed_width).max(10);
+
+    let summary = session
+        .summary
+        .as_deref()
+        .or(session.first_prompt.as_deref())
+        .unwrap_or("No summary");
+
+    // Color by provider; archived sessions are dimmed
+    let style = app.providers.style(&session.provider);
+    let summary_style = if row.selected {
+        Style::default().fg(Color::Cyan)
+    } else if is_archived {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default().fg(style.color)
+    };
+    let metadata_style = if row.selected {
+        Style::default().fg(Color::Gray)
+    } else if is_archived {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default().fg(style.dim_color)
+    };
+
+    // Text search shows the matching snippet in place of the summary
+    let mut session_spans = vec![Span::styled(
+        format!("{}{} ", row.indent, checkbox),
+        summary_style,
+    )];
+    match &session.snippet {
+        Some(snippet) => session_spans.extend(snippet_spans(snippet, summary_max, summary_style)),
+        None => session_spans.extend(highlight_spans(
+            summary,
+            row.matches,
+            summary_max,
+            summary_style,
+        )),
+    }
+    session_spans.push(Span::styled(format!(" • {}", metadata), metadata_style));
 
-                        let session_spans = vec![
-                            Span::styled(format!("        {} ", checkbox), summary_style),
-                            Span::styled(summary, summary_style),
-                            Span::styled(format!(" • {}", metadata), metadata_style),
-                        ];
+    let session_line = Line::from(session_spans);
+    if row.selected {
+        session_line.patch_style(Style::default().bg(Color::DarkGray))
+    } else {
+        session_line
+    }
+}
 
-                        let session_line = Line::from(session_spans);
+fn link_label(link: LinkKind) -> &'static str {
+    match link {
+        LinkKind::Resume => "resumed",
+        LinkKind::Compact => "compacted",
+        LinkKind::Sidechain => "subagent",
+    }
+}
 
-                        lines.push(if session_selected {
-                            session_line.patch_style(Style::default().bg(Color::DarkGray))
-                        } else {
-                            session_line
-                        });
-                    }
+/// The `session_columns` from the config, for sessions with recorded usage
+fn usage_columns(session: &SessionData, config: &Config) -> Vec<String> {
+    if session.usage.is_empty() {
+        return Vec::new();
+    }
+    config
+        .session_columns
+        .iter()
+        .map(|column| match column {
+            SessionColumn::Model => {
+                // Usage comes most output first, so the main model leads
+                let model = short_model(&session.usage[0].model);
+                match session.usage.len() {
+                    1 => model,
+                    n => format!("{} +{}", model, n - 1),
                 }
             }
+            SessionColumn::Tokens => {
+                let total: i64 = session.usage.iter().map(|u| u.tokens.total()).sum();
+                format!("{} tok", stats::format_tokens(total))
+            }
+            SessionColumn::Cost => stats::session_cost(&session.usage, config)
+                .map_or_else(|| "$?".to_string(), stats::format_cost),
+        })
+        .collect()
+}
+
+/// `claude-sonnet-4-5-20250929` as `sonnet-4-5`
+fn short_model(model: &str) -> String {
+    let model = model.strip_prefix("claude-").unwrap_or(model);
+    match model.rsplit_once('-') {
+        Some((name, date)) if date.len() == 8 && date.chars().all(|c| c.is_ascii_digit()) => {
+            name.to_string()
+        }
+        _ => model.to_string(),
+    }
+}
+
+/// Worktree name followed by a compact status, e.g. `feat REBASE ↑2↓1 !1 +3 ~2 ?1 $1`
+fn worktree_label(name: &str, status: &WorktreeStatus) -> String {
+    let mut parts = vec![name.to_string()];
+
+    if let Some(op) = status.operation {
+        parts.push(op.as_str().to_uppercase());
+    }
+
+    if status.upstream.is_some() && (status.ahead > 0 || status.behind > 0) {
+        let mut ab = String::new();
+        if status.ahead > 0 {
+            ab.push_str(&format!("↑{}", status.ahead));
+        }
+        if status.behind > 0 {
+            ab.push_str(&format!("↓{}", status.behind));
         }
+        parts.push(ab);
     }
 
-    let paragraph = Paragraph::new(lines).scroll((app.scroll_offset, 0));
-    f.render_widget(paragraph, area);
+    for (prefix, count) in [
+        ("!", status.conflicted),
+        ("+", status.staged),
+        ("~", status.unstaged),
+        ("?", status.untracked),
+        ("$", status.stash_count),
+    ] {
+        if count > 0 {
+            parts.push(format!("{}{}", prefix, count));
+        }
+    }
+
+    parts.join(" ")
+}
+
+/// A search snippet on one line, at most `max_len` chars, with matched terms
+/// (between `MATCH_START` and `MATCH_END`) highlighted
+fn snippet_spans(snippet: &str, max_len: usize, style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut highlighted = false;
+    let mut len = 0;
+
+    for c in snippet.chars() {
+        if c == MATCH_START || c == MATCH_END {
+            if !current.is_empty() {
+                spans.push(match_span(std::mem::take(&mut current), highlighted, style));
+            }
+            highlighted = c == MATCH_START;
+            continue;
+        }
+        if len == max_len {
+            break;
+        }
+        current.push(if c.is_whitespace() { ' ' } else { c });
+        len += 1;
+    }
+    if !current.is_empty() {
+        spans.push(match_span(current, highlighted, style));
+    }
+    spans
+}
+
+/// The first line of `text`, truncated to `max_len` chars, with the chars at
+/// `positions` (as found by the fuzzy filter) highlighted
+fn highlight_spans(
+    text: &str,
+    positions: &[usize],
+    max_len: usize,
+    style: Style,
+) -> Vec<Span<'static>> {
+    let first_line = text.lines().next().unwrap_or(text);
+    let truncated = first_line.chars().count() > max_len;
+    let visible = if truncated {
+        max_len.saturating_sub(3)
+    } else {
+        max_len
+    };
+
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut highlighted = false;
+    for (idx, c) in first_line.chars().take(visible).enumerate() {
+        let is_match = positions.binary_search(&idx).is_ok();
+        if is_match != highlighted && !current.is_empty() {
+            spans.push(match_span(std::mem::take(&mut current), highlighted, style));
+        }
+        highlighted = is_match;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        spans.push(match_span(current, highlighted, style));
+    }
+    if truncated {
+        spans.push(Span::styled("...", style));
+    }
+    spans
 }
 
-fn truncate_str(s: &str, max_len: usize) -> String {
-    // Take first line only
-    let first_line = s.lines().next().unwrap_or(s);
-    if first_line.len() <= max_len {
-        first_line.to_string()
+fn match_span(text: String, highlighted: bool, style: Style) -> Span<'static> {
+    if highlighted {
+        Span::styled(text, style.bold().underlined())
     } else {
-        format!("{}...", &first_line[..max_len - 3])
+        Span::styled(text, style)
     }
 }
 
@@ -0,0 +1,118 @@
+use crate::scanner::git::{Worktree, WorktreeStatus};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a status is trusted before it is refreshed again in the background
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Number of background threads running `git status`
+const WORKERS: usize = 4;
+
+struct Entry {
+    status: WorktreeStatus,
+    fetched_at: Instant,
+}
+
+/// Worktree git status keyed by worktree path.
+///
+/// Statuses are computed on background threads so filtering and navigation
+/// never wait on git; the UI polls for finished results and redraws.
+pub struct WorktreeCache {
+    entries: HashMap<PathBuf, Entry>,
+    pending: HashSet<PathBuf>,
+    requests: Sender<PathBuf>,
+    results: Receiver<(PathBuf, WorktreeStatus)>,
+}
+
+impl WorktreeCache {
+    pub fn new() -> Self {
+        let (requests, request_rx) = mpsc::channel::<PathBuf>();
+        let (result_tx, results) = mpsc::channel();
+        let request_rx = Arc::new(Mutex::new(request_rx));
+
+        for _ in 0..WORKERS {
+            let request_rx = Arc::clone(&request_rx);
+            let result_tx = result_tx.clone();
+            thread::spawn(move || {
+                loop {
+                    // Exits once the cache (and with it the request sender) is dropped
+                    let path = match request_rx.lock().unwrap().recv() {
+                        Ok(path) => path,
+                        Err(_) => break,
+                    };
+                    let worktree = Worktree {
+                        path: path.clone(),
+                        branch: None,
+                    };
+                    if result_tx.send((path, worktree.status())).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        WorktreeCache {
+            entries: HashMap::new(),
+            pending: HashSet::new(),
+            requests,
+            results,
+        }
+    }
+
+    /// Last known status, if one has been computed yet
+    pub fn get(&self, path: &Path) -> Option<&WorktreeStatus> {
+        self.entries.get(path).map(|e| &e.status)
+    }
+
+    /// Queue a background refresh for every path that is missing or stale
+    pub fn request<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        for path in paths {
+            if self.pending.contains(path) {
+                continue;
+            }
+            let fresh = self
+                .entries
+                .get(path)
+                .is_some_and(|e| e.fetched_at.elapsed() < MAX_AGE);
+            if !fresh && self.requests.send(path.to_path_buf()).is_ok() {
+                self.pending.insert(path.to_path_buf());
+            }
+        }
+    }
+
+    /// Collect finished background results. Returns true if anything changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok((path, status)) = self.results.try_recv() {
+            self.pending.remove(&path);
+            self.insert(path, status);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Compute a status synchronously, for decisions that can't use a stale value
+    pub fn refresh_now(&mut self, path: &Path) -> WorktreeStatus {
+        let worktree = Worktree {
+            path: path.to_path_buf(),
+            branch: None,
+        };
+        let status = worktree.status();
+        self.insert(path.to_path_buf(), status.clone());
+        status
+    }
+
+    fn insert(&mut self, path: PathBuf, status: WorktreeStatus) {
+        self.entries.insert(
+            path,
+            Entry {
+                status,
+                fetched_at: Instant::now(),
+            },
+        );
+    }
+}